@@ -0,0 +1,415 @@
+use std::future::poll_fn;
+
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+
+use crate::{Channel, ChannelReceiver, ChannelSender};
+
+/// The most slots [`Channel::send_all`] reserves at once.
+///
+/// Unbounded directions report a huge capacity, which would otherwise be waited for as a whole.
+const SEND_ALL_CHUNK: usize = 1024;
+
+/// An iterator over the messages queued in a direction when it was created, returned by
+/// [`Channel::drain`].
+///
+/// The iterator yields at most as many messages as were queued when it was created, and ends
+/// early once the queue is empty; it never waits for new messages.
+#[derive(Debug)]
+pub struct Drain<'a, R> {
+    receiver: &'a mut ChannelReceiver<R>,
+    remaining: usize,
+}
+
+impl<R> Iterator for Drain<'_, R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match self.receiver.try_recv() {
+            Ok(r) => {
+                self.remaining -= 1;
+                Some(r)
+            }
+            Err(_) => {
+                self.remaining = 0;
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<S> ChannelSender<S> {
+    /// Sends every message of `iter`, reserving capacity for as many of them as the direction can
+    /// hold at once instead of waiting for a slot per message.
+    ///
+    /// Messages are sent in order, taken from `iter` only once capacity is reserved for them, so
+    /// `iter` may be arbitrarily long. If the future is dropped, the messages that were already
+    /// sent stay sent and the others are still in `iter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The messages to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if every message was sent, or the next message
+    ///   of `iter` if the channel closed. The rest of `iter` is not consumed. If the direction has
+    ///   a dead-letter sink, that message goes to the sink instead and this returns `Ok(())`.
+    pub async fn send_all<I>(&self, iter: I) -> Result<(), SendError<S>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        let mut iter = iter.into_iter();
+
+        loop {
+            // Capacity is reserved before any message is taken from `iter`, so that dropping the
+            // future while it waits never loses a message. Free slots are taken as they are, and
+            // a full direction is waited on for a whole chunk.
+            let mut n = match self.capacity() {
+                Some(0) | None => SEND_ALL_CHUNK,
+                Some(free) => free,
+            };
+            n = n.min(self.max_capacity().unwrap_or(n)).min(SEND_ALL_CHUNK);
+            if let Some(upper) = iter.size_hint().1 {
+                n = n.min(upper);
+            }
+            if n == 0 {
+                return Ok(());
+            }
+
+            let Ok(permits) = self.reserve_many(n).await else {
+                return match iter.next() {
+                    None => Ok(()),
+                    Some(s) => self.undeliverable(s).map_err(SendError),
+                };
+            };
+
+            for permit in permits {
+                match iter.next() {
+                    Some(s) => permit.send(s),
+                    None => return Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Receives up to `limit` messages from the peer, waiting only if none are queued.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The vector the messages are appended to.
+    /// * `limit` - The maximum number of messages to receive.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Returns the number of messages received, which is `0` only if the channel is closed
+    ///   and empty or `limit` is `0`.
+    pub async fn recv_many(&mut self, buffer: &mut Vec<R>, limit: usize) -> usize {
+        let mut envelopes = Vec::new();
+
+        loop {
+            if poll_fn(|cx| self.queue().poll_recv_many(cx, &mut envelopes, limit)).await == 0 {
+                return 0;
+            }
+
+            // Messages that outlived the time to live of the direction are left out, so wait
+            // again if nothing else arrived.
+            let len = buffer.len();
+            buffer.extend(
+                envelopes
+                    .drain(..)
+                    .filter_map(|envelope| self.open(envelope)),
+            );
+            if buffer.len() > len {
+                return buffer.len() - len;
+            }
+        }
+    }
+
+    /// Receives up to `limit` messages that are already queued without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The vector the messages are appended to.
+    /// * `limit` - The maximum number of messages to receive.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Returns the number of messages received.
+    pub fn try_recv_many(&mut self, buffer: &mut Vec<R>, limit: usize) -> usize {
+        let mut received = 0;
+
+        while received < limit {
+            match self.try_recv() {
+                Ok(r) => buffer.push(r),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+            received += 1;
+        }
+
+        received
+    }
+
+    /// Returns an iterator over the messages that are currently queued.
+    pub fn drain(&mut self) -> Drain<'_, R> {
+        Drain {
+            remaining: self.queue_len(),
+            receiver: self,
+        }
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Sends every message of `iter`, reserving capacity for as many of them as the channel can
+    /// hold at once instead of waiting for a slot per message.
+    ///
+    /// Messages are sent in order, taken from `iter` only once capacity is reserved for them, so
+    /// `iter` may be arbitrarily long. If the future is dropped, the messages that were already
+    /// sent stay sent and the others are still in `iter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iter` - The messages to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if every message was sent, or the next message
+    ///   of `iter` if the channel closed. The rest of `iter` is not consumed. If the direction has
+    ///   a dead-letter sink, that message goes to the sink instead and this returns `Ok(())`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = channel::<u32, u32>(10);
+    ///
+    /// chan1.send_all(0..5).await.unwrap();
+    /// assert_eq!(chan2.drain().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+    /// # }
+    /// ```
+    pub async fn send_all<I>(&self, iter: I) -> Result<(), SendError<S>>
+    where
+        I: IntoIterator<Item = S>,
+    {
+        self.sender.send_all(iter).await
+    }
+
+    /// Receives up to `limit` messages from the channel, waiting only if none are queued.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The vector the messages are appended to.
+    /// * `limit` - The maximum number of messages to receive.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Returns the number of messages received, which is `0` only if the channel is closed
+    ///   and empty or `limit` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = channel::<u32, u32>(10);
+    /// chan1.send_all(0..5).await.unwrap();
+    ///
+    /// let mut buffer = Vec::new();
+    /// assert_eq!(chan2.recv_many(&mut buffer, 3).await, 3);
+    /// assert_eq!(chan2.recv_many(&mut buffer, 3).await, 2);
+    /// assert_eq!(buffer, vec![0, 1, 2, 3, 4]);
+    /// # }
+    /// ```
+    pub async fn recv_many(&mut self, buffer: &mut Vec<R>, limit: usize) -> usize {
+        self.receiver.recv_many(buffer, limit).await
+    }
+
+    /// Receives up to `limit` messages that are already queued without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The vector the messages are appended to.
+    /// * `limit` - The maximum number of messages to receive.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - Returns the number of messages received.
+    pub fn try_recv_many(&mut self, buffer: &mut Vec<R>, limit: usize) -> usize {
+        self.receiver.try_recv_many(buffer, limit)
+    }
+
+    /// Returns an iterator over the messages that are currently queued.
+    pub fn drain(&mut self) -> Drain<'_, R> {
+        self.receiver.drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use crate::channel;
+
+    /// Counts how many times the wrapped future is polled, i.e. how many times its task was woken.
+    struct CountPolls<F> {
+        inner: Pin<Box<F>>,
+        polls: usize,
+    }
+
+    impl<F: Future> Future for CountPolls<F> {
+        type Output = (F::Output, usize);
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.polls += 1;
+            let polls = self.polls;
+            self.inner.as_mut().poll(cx).map(|out| (out, polls))
+        }
+    }
+
+    fn count_polls<F: Future>(inner: F) -> CountPolls<F> {
+        CountPolls {
+            inner: Box::pin(inner),
+            polls: 0,
+        }
+    }
+
+    const MESSAGES: u32 = 10_000;
+
+    #[tokio::test]
+    async fn test_recv_many_fewer_wakeups() {
+        let (chan1, mut chan2) = channel::<u32, u32>(MESSAGES as usize);
+        chan1.send_all(0..MESSAGES).await.unwrap();
+
+        let (received, single_wakeups) = tokio::spawn(count_polls(async move {
+            let mut received = 0;
+            while received < MESSAGES {
+                chan2.recv().await.unwrap();
+                received += 1;
+            }
+            received
+        }))
+        .await
+        .unwrap();
+        assert_eq!(received, MESSAGES);
+
+        let (chan1, mut chan2) = channel::<u32, u32>(MESSAGES as usize);
+        chan1.send_all(0..MESSAGES).await.unwrap();
+
+        let (received, batch_wakeups) = tokio::spawn(count_polls(async move {
+            let mut buffer = Vec::new();
+            while buffer.len() < MESSAGES as usize {
+                chan2.recv_many(&mut buffer, 1024).await;
+            }
+            buffer
+        }))
+        .await
+        .unwrap();
+        assert_eq!(received, (0..MESSAGES).collect::<Vec<_>>());
+
+        assert!(
+            batch_wakeups * 10 < single_wakeups,
+            "recv_many woke {} times, recv woke {} times",
+            batch_wakeups,
+            single_wakeups
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_recv_many_and_drain() {
+        let (chan1, mut chan2) = channel::<u32, u32>(10);
+        chan1.send_all(0..6).await.unwrap();
+
+        let mut buffer = Vec::new();
+        assert_eq!(chan2.try_recv_many(&mut buffer, 4), 4);
+        assert_eq!(buffer, vec![0, 1, 2, 3]);
+
+        assert_eq!(chan2.drain().collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(chan2.try_recv_many(&mut buffer, 4), 0);
+        assert_eq!(chan2.drain().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_all_larger_than_capacity() {
+        let (chan1, mut chan2) = channel::<u32, u32>(4);
+
+        let consumer = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            while chan2.recv_many(&mut buffer, 16).await > 0 {}
+            buffer
+        });
+
+        chan1.send_all(0..100).await.unwrap();
+        drop(chan1);
+
+        assert_eq!(consumer.await.unwrap(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_send_all_closed() {
+        let (chan1, chan2) = channel::<u32, u32>(4);
+        drop(chan2);
+
+        let mut iter = 0..3;
+        assert_eq!(chan1.send_all(iter.by_ref()).await.unwrap_err().0, 0);
+        assert_eq!(iter.next(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_send_all_cancelled() {
+        use futures::FutureExt;
+
+        let (chan1, mut chan2) = channel::<u32, u32>(2);
+        let mut iter = 0..10;
+
+        // The future is dropped while it waits for the full direction to make room.
+        assert!(chan1.send_all(iter.by_ref()).now_or_never().is_none());
+
+        assert_eq!(chan2.drain().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(iter.next(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_send_all_streams() {
+        let (chan1, mut chan2) = channel::<u32, u32>(4);
+
+        // An endless iterator is only pulled as the peer makes room.
+        let sender = tokio::spawn(async move { chan1.send_all(0..).await });
+        for i in 0..100 {
+            assert_eq!(chan2.recv().await, Some(i));
+        }
+
+        drop(chan2);
+        let unsent = sender.await.unwrap().unwrap_err().0;
+        assert!(unsent >= 100);
+    }
+
+    #[tokio::test]
+    async fn test_drain_ends() {
+        let (chan1, mut chan2) = channel::<u32, u32>(4);
+        chan1.send_all(0..3).await.unwrap();
+
+        // Messages sent while draining are left for later.
+        let mut drained = Vec::new();
+        for r in chan2.drain() {
+            drained.push(r);
+            chan1.try_send(r + 3).unwrap();
+        }
+
+        assert_eq!(drained, vec![0, 1, 2]);
+        assert_eq!(chan2.recv_queue_len(), 3);
+    }
+}
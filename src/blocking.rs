@@ -0,0 +1,267 @@
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+use tokio::sync::mpsc::error::SendError;
+
+use crate::split::Queue;
+use crate::{Channel, ChannelReceiver, ChannelSender, Overflow};
+
+/// A blocking iterator over the messages received from the peer, returned by
+/// [`Channel::blocking_iter`].
+///
+/// The iterator ends once the peer stops sending and every queued message has been received.
+#[derive(Debug)]
+pub struct BlockingIter<'a, R> {
+    receiver: &'a mut ChannelReceiver<R>,
+}
+
+impl<R> Iterator for BlockingIter<'_, R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        self.receiver.blocking_recv()
+    }
+}
+
+/// Wakes a thread parked by [`block_on`].
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while the future is pending.
+///
+/// Panics within an asynchronous execution context, as Tokio's blocking methods do.
+fn block_on<F: Future>(future: F) -> F::Output {
+    assert!(
+        tokio::runtime::Handle::try_current().is_err(),
+        "Cannot block the current thread from within a runtime. This happens because a \
+         function attempted to block the current thread while the thread is being used to \
+         drive asynchronous tasks."
+    );
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+impl<S> ChannelSender<S> {
+    /// Sends a message to the peer, blocking the current thread until there is capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called within an asynchronous execution context.
+    pub fn blocking_send(&self, s: S) -> Result<(), SendError<S>> {
+        if self.state.overflow != Overflow::Block {
+            return self.send_now(s).map_err(|e| SendError(e.into_inner()));
+        }
+
+        // The message is stamped before waiting, so time spent blocked also counts as latency.
+        let since = self.start_blocking(1);
+        let result = self.sender.blocking_send(self.envelope(s));
+        self.stop_blocking(since);
+
+        match result {
+            Ok(()) => {
+                self.sent();
+                Ok(())
+            }
+            Err(e) => self.undeliverable(e.0.value).map_err(SendError),
+        }
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Receives a message from the peer, blocking the current thread until one is available.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<R>` - Returns `Some(message)` if a message was received, or `None` if the channel is closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called within an asynchronous execution context.
+    pub fn blocking_recv(&mut self) -> Option<R> {
+        loop {
+            let envelope = match &mut self.receiver {
+                Queue::Owned(receiver) => receiver.blocking_recv()?,
+                // Evicting senders lock the queue too, so it is only locked while polled and never
+                // while the thread is parked.
+                Queue::Shared(_) => block_on(poll_fn(|cx| self.queue().poll_recv(cx)))?,
+            };
+            if let Some(r) = self.open(envelope) {
+                return Some(r);
+            }
+        }
+    }
+
+    /// Returns an iterator that blocks the current thread waiting for each message.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if advanced within an asynchronous execution context.
+    pub fn blocking_iter(&mut self) -> BlockingIter<'_, R> {
+        BlockingIter { receiver: self }
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Sends a message through the channel, blocking the current thread until there is capacity.
+    ///
+    /// This lets threads without a Tokio runtime, such as FFI callbacks, talk to async tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called within an asynchronous execution context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio_bichannel::channel;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (mut chan1, mut chan2) = channel::<String, String>(10);
+    ///
+    ///     let handle = std::thread::spawn(move || {
+    ///         chan1.blocking_send("Hello from a plain thread".to_string()).unwrap();
+    ///         chan1.blocking_recv().unwrap()
+    ///     });
+    ///
+    ///     assert_eq!(chan2.recv().await.unwrap(), "Hello from a plain thread");
+    ///     chan2.send("Hello from a task".to_string()).await.unwrap();
+    ///     assert_eq!(handle.join().unwrap(), "Hello from a task");
+    /// }
+    /// ```
+    pub fn blocking_send(&self, s: S) -> Result<(), SendError<S>> {
+        self.sender.blocking_send(s)
+    }
+
+    /// Receives a message from the channel, blocking the current thread until one is available.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<R>` - Returns `Some(message)` if a message was received, or `None` if the channel is closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called within an asynchronous execution context.
+    pub fn blocking_recv(&mut self) -> Option<R> {
+        self.receiver.blocking_recv()
+    }
+
+    /// Returns an iterator that blocks the current thread waiting for each message.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if advanced within an asynchronous execution context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio_bichannel::channel;
+    ///
+    /// let (chan1, mut chan2) = channel::<u32, u32>(10);
+    ///
+    /// std::thread::spawn(move || {
+    ///     for i in 0..3 {
+    ///         chan1.blocking_send(i).unwrap();
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(chan2.blocking_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+    /// ```
+    pub fn blocking_iter(&mut self) -> BlockingIter<'_, R> {
+        self.receiver.blocking_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::channel;
+
+    #[test]
+    fn test_blocking_threads() {
+        let (mut chan1, mut chan2) = channel::<String, String>(1);
+
+        let handle = thread::spawn(move || {
+            chan1.blocking_send("Hello from chan1".to_string()).unwrap();
+            let msg = chan1.blocking_recv().unwrap();
+            assert_eq!(msg, "Hello from chan2");
+        });
+
+        let msg = chan2.blocking_recv().unwrap();
+        assert_eq!(msg, "Hello from chan1");
+        chan2.blocking_send("Hello from chan2".to_string()).unwrap();
+
+        handle.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocking_recv_unlocks_evicting_direction() {
+        use std::time::Duration;
+
+        use crate::{ChannelBuilder, Direction, Overflow};
+
+        let (chan1, mut chan2) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(1).overflow(Overflow::EvictOldest))
+            .build::<u32, u32>();
+
+        let handle = thread::spawn(move || chan2.blocking_recv());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The only slot is reserved, so the send looks for a message to evict in the queue the
+        // thread is parked on, and must not wait for it.
+        let permit = chan1.reserve().await.unwrap();
+        chan1.try_send(5).unwrap();
+        assert_eq!(chan1.discarded(), 1);
+
+        permit.send(6);
+        assert_eq!(handle.join().unwrap(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_blocking_iter_with_task() {
+        let (chan1, mut chan2) = channel::<u32, u32>(2);
+
+        let handle = thread::spawn(move || chan2.blocking_iter().sum::<u32>());
+
+        for i in 1..=100 {
+            chan1.send(i).await.unwrap();
+        }
+        drop(chan1);
+
+        assert_eq!(handle.join().unwrap(), 5050);
+    }
+}
@@ -0,0 +1,372 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tokio::sync::mpsc::channel as create_channel;
+use tokio::sync::{Notify, Semaphore};
+
+use crate::split::{DirectionState, Queue};
+use crate::{Channel, ChannelReceiver, ChannelSender, Overflow};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The configuration of one direction of a channel, used with [`ChannelBuilder`].
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::Direction;
+///
+/// let commands = Direction::bounded(4).name("commands");
+/// let telemetry = Direction::unbounded().name("telemetry");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Direction {
+    capacity: Option<usize>,
+    name: Option<Arc<str>>,
+    metrics: bool,
+    overflow: Overflow,
+    ttl: Option<Duration>,
+}
+
+impl Direction {
+    /// Creates a direction that holds at most `capacity` queued messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The size of the buffer for this direction.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the channel is built if `capacity` is zero.
+    pub fn bounded(capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            name: None,
+            metrics: false,
+            overflow: Overflow::Block,
+            ttl: None,
+        }
+    }
+
+    /// Creates a direction with no limit on the number of queued messages.
+    ///
+    /// Sends in an unbounded direction never wait for capacity.
+    pub fn unbounded() -> Self {
+        Self {
+            capacity: None,
+            name: None,
+            metrics: false,
+            overflow: Overflow::Block,
+            ttl: None,
+        }
+    }
+
+    /// Sets the name shown when debugging the halves of this direction.
+    ///
+    /// With the `tracing` feature, the name is also attached to every event of the direction so
+    /// that a stuck channel can be told apart from the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the direction.
+    pub fn name(mut self, name: impl Into<Arc<str>>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Records metrics for this direction, read with [`Channel::stats`].
+    ///
+    /// This adds a timestamp to every message and a few atomic operations to every send and
+    /// receive.
+    pub fn metrics(mut self) -> Self {
+        self.metrics = true;
+        self
+    }
+
+    /// Sets what happens to messages sent while this direction is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `overflow` - The policy to apply, [`Overflow::Block`] by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio_bichannel::{Direction, Overflow};
+    ///
+    /// let telemetry = Direction::bounded(1024).overflow(Overflow::EvictOldest);
+    /// ```
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets how long a message may stay queued in this direction.
+    ///
+    /// A message that is still queued after `ttl` is discarded instead of being received, and
+    /// handed to the dead-letter sink of the direction if one was set with
+    /// [`Channel::set_dead_letter_sink`]. This adds a timestamp to every message.
+    ///
+    /// Expiry is only checked when receiving, so expired messages keep taking up capacity, and
+    /// are counted by `queue_len`, until the receiver gets to them. A sender may therefore wait
+    /// for room, or see the direction as full, while every queued message has expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - The time to live of the messages of this direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tokio_bichannel::Direction;
+    ///
+    /// let quotes = Direction::bounded(64).ttl(Duration::from_millis(500));
+    /// ```
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns whether every message sent in this direction is received, unless the receiver
+    /// goes away first.
+    #[cfg(feature = "durable")]
+    pub(crate) fn is_lossless(&self) -> bool {
+        self.overflow == Overflow::Block && self.ttl.is_none()
+    }
+
+    /// Creates the halves of this direction on their own, outside of a [`Channel`].
+    #[cfg(feature = "durable")]
+    pub(crate) fn create_detached<T>(&self) -> (ChannelSender<T>, ChannelReceiver<T>) {
+        let from = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let to = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        self.create(from, to, false)
+    }
+
+    /// Creates the halves of this direction, owned by the channels `from` and `to`.
+    ///
+    /// `metrics` records metrics even if the direction itself does not ask for them.
+    fn create<T>(
+        &self,
+        from: u64,
+        to: u64,
+        metrics: bool,
+    ) -> (ChannelSender<T>, ChannelReceiver<T>) {
+        // A bounded channel only allocates as messages are queued, so the
+        // largest possible bound behaves as an unbounded channel while keeping
+        // the bounded API (permits, capacity) available.
+        let max_capacity = self.capacity.unwrap_or(Semaphore::MAX_PERMITS);
+        let (tx, rx) = create_channel(max_capacity);
+        let (queue, shared) = match self.overflow {
+            Overflow::EvictOldest => {
+                let queue = Arc::new(Mutex::new(rx));
+                let shared = Arc::downgrade(&queue);
+                (Queue::Shared(queue), shared)
+            }
+            _ => (Queue::Owned(rx), Weak::new()),
+        };
+
+        let state = Arc::new(DirectionState {
+            name: self.name.clone(),
+            #[cfg(feature = "tracing")]
+            label: self
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{}->{}", from, to).into()),
+            max_capacity,
+            unbounded: self.capacity.is_none(),
+            metrics: (self.metrics || metrics).then(Default::default),
+            overflow: self.overflow,
+            ttl: self.ttl,
+            dropped: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+            dead_letters: Mutex::new(None),
+            queue: shared,
+            receiver_closed: AtomicBool::new(false),
+            receiver_closed_notify: Notify::new(),
+            #[cfg(feature = "remote")]
+            remote_error: Default::default(),
+        });
+
+        (
+            ChannelSender::new(from, state.clone(), tx),
+            ChannelReceiver::new(to, state, queue),
+        )
+    }
+}
+
+/// A builder for bidirectional channels whose directions are configured independently.
+///
+/// The "left" channel is the first one returned by [`ChannelBuilder::build`], the "right"
+/// channel is the second one.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::{ChannelBuilder, Direction};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (mut controller, mut device) = ChannelBuilder::new(16)
+///         .left_to_right(Direction::bounded(4).name("commands"))
+///         .right_to_left(Direction::bounded(4096).name("telemetry"))
+///         .build::<&str, u32>();
+///
+///     controller.send("start").await.unwrap();
+///     device.send(42).await.unwrap();
+///
+///     assert_eq!(device.recv().await.unwrap(), "start");
+///     assert_eq!(controller.recv().await.unwrap(), 42);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelBuilder {
+    left_to_right: Direction,
+    right_to_left: Direction,
+    metrics: bool,
+}
+
+impl ChannelBuilder {
+    /// Creates a builder where both directions are bounded by `buffer_size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer_size` - The default size of the buffer for each direction.
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            left_to_right: Direction::bounded(buffer_size),
+            right_to_left: Direction::bounded(buffer_size),
+            metrics: false,
+        }
+    }
+
+    /// Configures the direction carrying messages from the left channel to the right channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - The configuration of the direction.
+    pub fn left_to_right(mut self, direction: Direction) -> Self {
+        self.left_to_right = direction;
+        self
+    }
+
+    /// Configures the direction carrying messages from the right channel to the left channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `direction` - The configuration of the direction.
+    pub fn right_to_left(mut self, direction: Direction) -> Self {
+        self.right_to_left = direction;
+        self
+    }
+
+    /// Records metrics for both directions, read with [`Channel::stats`], whatever the
+    /// [`Direction`]s passed to the builder say.
+    pub fn metrics(mut self) -> Self {
+        self.metrics = true;
+        self
+    }
+
+    /// Creates the pair of channels.
+    ///
+    /// # Returns
+    ///
+    /// * `(Channel<T, U>, Channel<U, T>)` - Returns the left and right channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a bounded direction was configured with a capacity of zero.
+    pub fn build<T, U>(&self) -> (Channel<T, U>, Channel<U, T>) {
+        let l = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let r = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        let (ls, rr) = self.left_to_right.create(l, r, self.metrics);
+        let (rs, lr) = self.right_to_left.create(r, l, self.metrics);
+
+        (
+            Channel {
+                sender: ls,
+                receiver: lr,
+            },
+            Channel {
+                sender: rs,
+                receiver: rr,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_asymmetric_capacity() {
+        let (left, mut right) = ChannelBuilder::new(8)
+            .left_to_right(Direction::bounded(2))
+            .build::<u32, u32>();
+
+        left.try_send(1).unwrap();
+        left.try_send(2).unwrap();
+        assert!(left.try_send(3).is_err());
+
+        for i in 0..8 {
+            right.try_send(i).unwrap();
+        }
+        assert!(right.try_send(8).is_err());
+
+        assert_eq!(right.recv().await.unwrap(), 1);
+        left.try_send(3).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_direction() {
+        let (left, mut right) = ChannelBuilder::new(1)
+            .left_to_right(Direction::unbounded())
+            .build::<u32, u32>();
+
+        for i in 0..10_000 {
+            left.try_send(i).unwrap();
+        }
+        assert_eq!(left.max_capacity(), None);
+        assert_eq!(left.capacity(), None);
+        assert_eq!(left.send_queue_len(), 10_000);
+        assert_eq!(right.max_capacity(), Some(1));
+
+        for i in 0..10_000 {
+            assert_eq!(right.recv().await.unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn test_metrics_before_directions() {
+        let (left, _right) = ChannelBuilder::new(1)
+            .metrics()
+            .left_to_right(Direction::bounded(1))
+            .right_to_left(Direction::unbounded())
+            .build::<(), ()>();
+
+        let stats = left.stats();
+        assert!(stats.outgoing.is_some());
+        assert!(stats.incoming.is_some());
+    }
+
+    #[test]
+    fn test_names() {
+        let (left, right) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(1).name("commands"))
+            .right_to_left(Direction::unbounded().name("telemetry"))
+            .build::<(), ()>();
+
+        let (ltx, lrx) = left.split();
+        let (rtx, rrx) = right.split();
+
+        assert_eq!(ltx.name(), Some("commands"));
+        assert_eq!(rrx.name(), Some("commands"));
+        assert_eq!(rtx.name(), Some("telemetry"));
+        assert_eq!(lrx.name(), Some("telemetry"));
+        assert!(format!("{:?}", ltx).contains("commands"));
+    }
+}
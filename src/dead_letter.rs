@@ -0,0 +1,374 @@
+use std::sync::PoisonError;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::split::DirectionState;
+use crate::{Channel, ChannelReceiver, ChannelSender};
+
+/// Why a message ended up in a dead-letter sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The peer stopped receiving, either before the message was sent or while it was still
+    /// queued.
+    Closed,
+    /// The message was sent on a full direction with [`Overflow::DropNewest`].
+    ///
+    /// [`Overflow::DropNewest`]: crate::Overflow::DropNewest
+    Dropped,
+    /// The message was evicted from a full direction with [`Overflow::EvictOldest`].
+    ///
+    /// [`Overflow::EvictOldest`]: crate::Overflow::EvictOldest
+    Evicted,
+    /// The message stayed queued for longer than the time to live of the direction, set with
+    /// [`Direction::ttl`]. It is reported once the receiver reaches it.
+    ///
+    /// [`Direction::ttl`]: crate::Direction::ttl
+    Expired,
+}
+
+/// A message that could not be delivered, handed to the sink set with
+/// [`Channel::set_dead_letter_sink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter<T> {
+    /// The message that was not delivered.
+    pub message: T,
+    /// Why the message was not delivered.
+    pub reason: DeadLetterReason,
+}
+
+impl<T> DirectionState<T> {
+    /// Hands a message that will never be received to the dead-letter sink of the direction.
+    ///
+    /// The message is given back if there is no sink, or if the sink is no longer read.
+    pub(crate) fn dead_letter(&self, message: T, reason: DeadLetterReason) -> Result<(), T> {
+        let sink = self
+            .dead_letters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        match sink.as_ref() {
+            Some(sink) => sink
+                .send(DeadLetter { message, reason })
+                .map_err(|e| e.0.message),
+            None => Err(message),
+        }
+    }
+}
+
+impl<S> ChannelSender<S> {
+    /// Hands a message sent on a closed direction to the dead-letter sink, giving it back if
+    /// there is none.
+    ///
+    /// Messages sent after this half was closed with [`ChannelSender::close`] are always given
+    /// back, since the peer did not lose them.
+    pub(crate) fn undeliverable(&self, s: S) -> Result<(), S> {
+        if !self.routes_undeliverable() {
+            return Err(s);
+        }
+
+        self.state.dead_letter(s, DeadLetterReason::Closed)
+    }
+
+    /// Returns whether a message sent on a closed direction would go to the dead-letter sink.
+    pub(crate) fn routes_undeliverable(&self) -> bool {
+        self.closed.is_none()
+            && self
+                .state
+                .dead_letters
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_some()
+    }
+
+    /// Sets the sink receiving the messages of this direction that can never be received.
+    ///
+    /// See [`Channel::set_dead_letter_sink`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - Where to send undelivered messages.
+    pub fn set_dead_letter_sink(&self, sink: UnboundedSender<DeadLetter<S>>) {
+        *self
+            .state
+            .dead_letters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(sink);
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Stops receiving from the peer and returns the messages that are still queued.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<R>` - Returns the messages the peer sent that were not received yet, in order.
+    pub fn into_undelivered(mut self) -> Vec<R> {
+        self.close();
+        self.drain().collect()
+    }
+}
+
+/// Hands the messages still queued when the receiving half is dropped to the dead-letter sink,
+/// if the direction has one.
+impl<R> Drop for ChannelReceiver<R> {
+    fn drop(&mut self) {
+        self.state.close_receiver();
+
+        if self
+            .state
+            .dead_letters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_none()
+        {
+            return;
+        }
+
+        let mut queue = self.receiver.lock();
+        queue.close();
+        while let Ok(envelope) = queue.try_recv() {
+            let _ = self
+                .state
+                .dead_letter(envelope.value, DeadLetterReason::Closed);
+        }
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Sets the sink receiving the messages sent to the peer that can never be received, instead
+    /// of losing them.
+    ///
+    /// Each message is paired with the [`DeadLetterReason`] it was not delivered:
+    ///
+    /// * The peer stopped receiving. Sends that would fail with the message handed back return
+    ///   `Ok(())` instead, so a successful send no longer means the message was queued for the
+    ///   peer: use [`Channel::closed`] or [`Channel::is_closed`] to notice the peer is gone.
+    ///   Messages still queued when the peer is dropped end up in the sink too.
+    /// * The [`Overflow`] policy of the direction discarded the message.
+    /// * The message outlived the time to live of the direction. This is only noticed when the
+    ///   message would have been received, see [`Direction::ttl`].
+    ///
+    /// This applies to `send`, `try_send`, their timed and blocking variants, `send_all` and
+    /// the [`Sink`] implementation. Reserving capacity with `reserve` and its variants still
+    /// fails once the peer is gone, since there is no message yet. Sends after this end called
+    /// [`Channel::close_send`] fail as usual, because the peer did not lose anything.
+    ///
+    /// The sink is shared by every clone of the sending half, and replaces any sink set before.
+    /// Once the receiving end of the sink is dropped, sends fail as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - Where to send undelivered messages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio_bichannel::{channel, DeadLetter, DeadLetterReason};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, chan2) = channel::<String, String>(10);
+    /// let (sink, mut dead_letters) = tokio::sync::mpsc::unbounded_channel();
+    /// chan1.set_dead_letter_sink(sink);
+    ///
+    /// chan1.send("queued".to_string()).await.unwrap();
+    /// drop(chan2);
+    /// chan1.send("too late".to_string()).await.unwrap();
+    ///
+    /// for message in ["queued", "too late"] {
+    ///     let dead_letter = dead_letters.recv().await.unwrap();
+    ///     let reason = DeadLetterReason::Closed;
+    ///     assert_eq!(dead_letter, DeadLetter { message: message.to_string(), reason });
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`Overflow`]: crate::Overflow
+    /// [`Direction::ttl`]: crate::Direction::ttl
+    /// [`Sink`]: https://docs.rs/futures-sink/latest/futures_sink/trait.Sink.html
+    pub fn set_dead_letter_sink(&self, sink: UnboundedSender<DeadLetter<S>>) {
+        self.sender.set_dead_letter_sink(sink);
+    }
+
+    /// Tears the channel down, returning the messages from the peer that were not received yet.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<R>` - Returns the messages still queued to be received, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, chan2) = channel::<u32, u32>(10);
+    /// chan2.send(1).await.unwrap();
+    /// chan2.send(2).await.unwrap();
+    ///
+    /// assert_eq!(chan1.into_undelivered(), vec![1, 2]);
+    /// assert!(chan2.send(3).await.is_err());
+    /// # }
+    /// ```
+    pub fn into_undelivered(self) -> Vec<R> {
+        self.receiver.into_undelivered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    use super::*;
+    use crate::{channel, ChannelBuilder, Direction, Overflow};
+
+    fn reasons<T>(
+        dead_letters: &mut UnboundedReceiver<DeadLetter<T>>,
+    ) -> Vec<(T, DeadLetterReason)> {
+        let mut reasons = Vec::new();
+        while let Ok(dead_letter) = dead_letters.try_recv() {
+            reasons.push((dead_letter.message, dead_letter.reason));
+        }
+        reasons
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let (chan1, chan2) = channel::<u32, u32>(4);
+        let (sink, mut dead_letters) = unbounded_channel();
+        chan1.set_dead_letter_sink(sink);
+
+        chan1.send(1).await.unwrap();
+        chan1.send(2).await.unwrap();
+        drop(chan2);
+        chan1.send(3).await.unwrap();
+        chan1.try_send(4).unwrap();
+
+        assert_eq!(
+            reasons(&mut dead_letters),
+            (1..=4)
+                .map(|i| (i, DeadLetterReason::Closed))
+                .collect::<Vec<_>>()
+        );
+
+        // Without anyone reading the sink, sends fail as before.
+        drop(dead_letters);
+        assert_eq!(chan1.send(5).await.unwrap_err().0, 5);
+    }
+
+    #[tokio::test]
+    async fn test_closed_by_sender() {
+        let (mut chan1, _chan2) = channel::<u32, u32>(4);
+        let (sink, mut dead_letters) = unbounded_channel();
+        chan1.set_dead_letter_sink(sink);
+
+        chan1.close_send();
+        assert_eq!(chan1.send(1).await.unwrap_err().0, 1);
+        assert!(chan1.try_send(2).is_err());
+        assert!(reasons(&mut dead_letters).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_all_and_sink() {
+        let (chan1, chan2) = channel::<u32, u32>(2);
+        let (sink, mut dead_letters) = unbounded_channel();
+        chan1.set_dead_letter_sink(sink);
+        drop(chan2);
+
+        // Only the next message goes to the sink, so an endless iterator still ends.
+        chan1.send_all(0..).await.unwrap();
+
+        #[cfg(feature = "futures")]
+        {
+            use futures::SinkExt;
+
+            let (mut tx, _rx) = chan1.split();
+            SinkExt::send(&mut tx, 5).await.unwrap();
+        }
+
+        let mut expected = vec![(0, DeadLetterReason::Closed)];
+        if cfg!(feature = "futures") {
+            expected.push((5, DeadLetterReason::Closed));
+        }
+        assert_eq!(reasons(&mut dead_letters), expected);
+    }
+
+    #[tokio::test]
+    async fn test_overflow() {
+        let (sink, mut dead_letters) = unbounded_channel();
+
+        let (chan1, _chan2) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(1).overflow(Overflow::DropNewest))
+            .build::<u32, u32>();
+        chan1.set_dead_letter_sink(sink.clone());
+        chan1.send(1).await.unwrap();
+        chan1.send(2).await.unwrap();
+
+        let (chan1, mut chan2) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(1).overflow(Overflow::EvictOldest))
+            .build::<u32, u32>();
+        chan1.set_dead_letter_sink(sink);
+        chan1.send(3).await.unwrap();
+        chan1.send(4).await.unwrap();
+
+        assert_eq!(chan2.recv().await, Some(4));
+        assert_eq!(
+            reasons(&mut dead_letters),
+            vec![
+                (2, DeadLetterReason::Dropped),
+                (3, DeadLetterReason::Evicted)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired() {
+        let (chan1, mut chan2) = ChannelBuilder::new(4)
+            .left_to_right(Direction::bounded(4).ttl(Duration::from_millis(20)))
+            .metrics()
+            .build::<u32, u32>();
+        let (sink, mut dead_letters) = unbounded_channel();
+        chan1.set_dead_letter_sink(sink);
+
+        chan1.send(1).await.unwrap();
+        chan1.send(2).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        chan1.send(3).await.unwrap();
+
+        assert_eq!(chan2.recv().await, Some(3));
+        assert_eq!(
+            reasons(&mut dead_letters),
+            vec![
+                (1, DeadLetterReason::Expired),
+                (2, DeadLetterReason::Expired)
+            ]
+        );
+
+        let stats = chan2.stats().incoming.unwrap();
+        assert_eq!(stats.expired, 2);
+        assert_eq!(stats.queue_depth, 0);
+
+        // Expired messages are skipped by every way of receiving.
+        chan1.send(4).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        chan1.send(5).await.unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(chan2.recv_many(&mut buffer, 10).await, 1);
+        assert_eq!(buffer, vec![5]);
+    }
+
+    #[tokio::test]
+    async fn test_into_undelivered() {
+        let (chan1, chan2) = channel::<u32, u32>(4);
+        let (tx, rx) = chan1.split();
+
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        assert_eq!(chan2.into_undelivered(), vec![1, 2]);
+
+        drop(rx);
+        assert!(tx.send(3).await.is_err());
+    }
+}
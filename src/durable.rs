@@ -0,0 +1,645 @@
+//! Channel directions whose messages survive restarts, kept in a write-ahead log on disk.
+//!
+//! A durable direction is configured with a [`Direction`], like the directions of a
+//! [`ChannelBuilder`], which sets the capacity and the name of the queue between its halves.
+//! On top of that, every message is written and synced to the log before it is queued, so
+//! [`DurableSender::send`] can fail with an I/O error, and stays in the log until the receiver
+//! acknowledges it with the sequence number returned by [`DurableReceiver::recv`].
+//!
+//! [`ChannelBuilder`]: crate::ChannelBuilder
+
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use fs4::{FileExt, TryLockError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use crate::remote::{Codec, RemoteError};
+use crate::{ChannelReceiver, ChannelSender, Direction};
+
+/// Compaction waits for at least this many bytes of records that are no longer needed.
+const COMPACT_MIN_GARBAGE: u64 = 4096;
+
+/// Error returned by the operations of a durable direction.
+#[derive(Debug)]
+pub enum DurableError {
+    /// Reading, writing or locking the log failed.
+    Io(io::Error),
+    /// A record could not be encoded, or a record read from the log could not be decoded.
+    Codec(RemoteError),
+    /// Another direction is using the log.
+    Locked,
+}
+
+impl fmt::Display for DurableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurableError::Io(e) => write!(f, "log error: {}", e),
+            DurableError::Codec(e) => write!(f, "log record error: {}", e),
+            DurableError::Locked => write!(f, "log is used by another direction"),
+        }
+    }
+}
+
+impl Error for DurableError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DurableError::Io(e) => Some(e),
+            DurableError::Codec(e) => Some(e),
+            DurableError::Locked => None,
+        }
+    }
+}
+
+impl From<io::Error> for DurableError {
+    fn from(e: io::Error) -> Self {
+        DurableError::Io(e)
+    }
+}
+
+impl From<RemoteError> for DurableError {
+    fn from(e: RemoteError) -> Self {
+        match e {
+            RemoteError::Io(e) => DurableError::Io(e),
+            e => DurableError::Codec(e),
+        }
+    }
+}
+
+/// A record of the log.
+#[derive(Debug, Serialize, Deserialize)]
+enum Record<T> {
+    /// Starts a compacted log, so that sequence numbers carry on after every message in it was
+    /// acknowledged.
+    Header {
+        next_seq: u64,
+    },
+    Message {
+        seq: u64,
+        value: T,
+    },
+    Ack {
+        seq: u64,
+    },
+}
+
+#[derive(Debug)]
+struct Log<C> {
+    path: PathBuf,
+    file: File,
+    codec: C,
+    next_seq: u64,
+    // The encoded records of the messages not acknowledged yet, to rewrite them when compacting.
+    unacked: BTreeMap<u64, BytesMut>,
+    // The size of the log, and how much of it is still needed.
+    len: u64,
+    live: u64,
+    // Held for as long as the direction is open.
+    _lock: std::fs::File,
+}
+
+impl<C: Codec> Log<C> {
+    /// Appends `buf` to the log and waits until it is on disk.
+    async fn append(&mut self, buf: &[u8]) -> Result<(), DurableError> {
+        self.file.write_all(buf).await?;
+        self.file.sync_data().await?;
+        self.len += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Appends a message to the log, returning its sequence number.
+    async fn push<T: Serialize>(&mut self, value: &T) -> Result<u64, DurableError> {
+        let seq = self.next_seq;
+        let mut buf = BytesMut::new();
+        self.codec
+            .encode(&Record::Message { seq, value }, &mut buf)?;
+        self.append(&buf).await?;
+
+        self.next_seq += 1;
+        self.live += buf.len() as u64;
+        self.unacked.insert(seq, buf);
+        Ok(seq)
+    }
+
+    /// Appends an acknowledgement to the log, compacting it once most of it is no longer
+    /// needed.
+    async fn ack(&mut self, seq: u64) -> Result<(), DurableError> {
+        if !self.unacked.contains_key(&seq) {
+            return Ok(());
+        }
+
+        let mut buf = BytesMut::new();
+        self.codec.encode(&Record::<()>::Ack { seq }, &mut buf)?;
+        self.append(&buf).await?;
+        if let Some(message) = self.unacked.remove(&seq) {
+            self.live -= message.len() as u64;
+        }
+
+        let garbage = self.len - self.live;
+        if garbage >= COMPACT_MIN_GARBAGE && garbage >= self.live {
+            self.compact().await?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log with a header and the unacknowledged messages only.
+    async fn compact(&mut self) -> Result<(), DurableError> {
+        let buf = snapshot(&mut self.codec, self.next_seq, &self.unacked)?;
+        self.file = rewrite(&self.path, &buf).await?;
+        self.len = buf.len() as u64;
+        self.live = self.len;
+        Ok(())
+    }
+}
+
+/// Encodes a compacted log holding the header and the given encoded messages.
+fn snapshot<C: Codec>(
+    codec: &mut C,
+    next_seq: u64,
+    unacked: &BTreeMap<u64, BytesMut>,
+) -> Result<BytesMut, DurableError> {
+    let mut buf = BytesMut::new();
+    codec.encode(&Record::<()>::Header { next_seq }, &mut buf)?;
+    for message in unacked.values() {
+        buf.extend_from_slice(message);
+    }
+    Ok(buf)
+}
+
+/// Replaces the log at `path` with `contents` at once, so that a crash meanwhile leaves either
+/// intact, and opens it for appending.
+async fn rewrite(path: &Path, contents: &[u8]) -> io::Result<File> {
+    let mut compacted = path.as_os_str().to_owned();
+    compacted.push(".compact");
+
+    let mut file = File::create(&compacted).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&compacted, path).await?;
+
+    OpenOptions::new().append(true).open(path).await
+}
+
+/// Takes the advisory lock on the log at `path`, kept on a file next to it.
+async fn lock(path: &Path) -> Result<std::fs::File, DurableError> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .await?
+        .into_std()
+        .await;
+
+    // Called through the trait, which the inherent `File::try_lock` of newer Rust versions
+    // would otherwise shadow.
+    match FileExt::try_lock(&file) {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(DurableError::Locked),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// The sending half of a durable direction, created with [`channel`].
+///
+/// Every message is appended to the log, and synced to disk, before it is queued for the
+/// receiver. The sender can be cloned to send from several tasks.
+#[derive(Debug)]
+pub struct DurableSender<T, C> {
+    log: Arc<Mutex<Log<C>>>,
+    sender: ChannelSender<(u64, T)>,
+}
+
+impl<T, C> Clone for DurableSender<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            log: self.log.clone(),
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T, C> DurableSender<T, C> {
+    /// The name of the direction, if one was set with [`Direction::name`].
+    pub fn name(&self) -> Option<&str> {
+        self.sender.name()
+    }
+}
+
+impl<T, C> DurableSender<T, C>
+where
+    T: Serialize,
+    C: Codec,
+{
+    /// Writes a message to the log and queues it for the receiver, waiting for capacity.
+    ///
+    /// If the receiver is gone, the message is still written to the log, and delivered after
+    /// the log is opened again.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<u64, DurableError>` - Returns the sequence number of the message, or an error if it could not be
+    ///   written to the log.
+    pub async fn send(&self, value: T) -> Result<u64, DurableError> {
+        let permit = self.sender.reserve().await;
+
+        // Queued while the log is locked, so the receiver sees messages in log order.
+        let mut log = self.log.lock().await;
+        let seq = log.push(&value).await?;
+
+        if let Ok(permit) = permit {
+            permit.send((seq, value));
+        }
+
+        Ok(seq)
+    }
+}
+
+/// The receiving half of a durable direction, created with [`channel`].
+///
+/// Received messages stay in the log until they are acknowledged with
+/// [`DurableReceiver::ack`], so messages received but not acknowledged before a restart are
+/// received again.
+#[derive(Debug)]
+pub struct DurableReceiver<T, C> {
+    log: Arc<Mutex<Log<C>>>,
+    // Messages recovered from the log when it was opened.
+    replay: VecDeque<(u64, T)>,
+    receiver: ChannelReceiver<(u64, T)>,
+}
+
+impl<T, C> DurableReceiver<T, C>
+where
+    C: Codec,
+{
+    /// Receives the next message, starting with the unacknowledged messages recovered from
+    /// the log.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(u64, T)>` - Returns the sequence number and the message, or `None` if every sender is gone.
+    pub async fn recv(&mut self) -> Option<(u64, T)> {
+        match self.replay.pop_front() {
+            Some(message) => Some(message),
+            None => self.receiver.recv().await,
+        }
+    }
+
+    /// Acknowledges a message, removing it from the log.
+    ///
+    /// Messages can be acknowledged in any order. Once the records that are no longer needed
+    /// make up most of the log, it is compacted to the unacknowledged messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `seq` - The sequence number of the message, as returned by [`DurableReceiver::recv`].
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), DurableError>` - Returns `Ok(())` once the acknowledgement is on disk, or an error if it
+    ///   could not be written or the log could not be compacted.
+    pub async fn ack(&self, seq: u64) -> Result<(), DurableError> {
+        self.log.lock().await.ack(seq).await
+    }
+
+    /// Returns the number of messages in the log that are not acknowledged yet.
+    pub async fn unacked(&self) -> usize {
+        self.log.lock().await.unacked.len()
+    }
+
+    /// The name of the direction, if one was set with [`Direction::name`].
+    pub fn name(&self) -> Option<&str> {
+        self.receiver.name()
+    }
+}
+
+/// Opens a durable direction backed by the log at `path`, creating it if needed.
+///
+/// Messages left unacknowledged in the log are received first, in the order they were sent,
+/// and sequence numbers carry on from the last message ever sent. The log is compacted to those
+/// messages when it is opened; a record cut short by a crash at the end of the log is dropped,
+/// since it was never reported as sent.
+///
+/// Only one direction may use a log at a time. This is enforced with an advisory lock on a file
+/// next to the log, with `.lock` appended to its name, held until both halves are dropped.
+///
+/// # Arguments
+///
+/// * `path` - The path of the log file.
+/// * `codec` - The codec used to encode the records of the log.
+/// * `direction` - The configuration of the queue between the halves, which sets how many
+///   messages are queued for the receiver before `send` waits.
+///
+/// # Returns
+///
+/// * `Result<(DurableSender<T, C>, DurableReceiver<T, C>), DurableError>` - Returns the two halves, or an error if
+///   the log could not be read or written, or is locked by another direction.
+///
+/// # Panics
+///
+/// Panics if `direction` could drop queued messages, because of an [`Overflow`] policy other
+/// than [`Overflow::Block`] or a [`Direction::ttl`]. They would only come back after a restart.
+///
+/// [`Overflow`]: crate::Overflow
+/// [`Overflow::Block`]: crate::Overflow::Block
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use tokio_bichannel::remote::Json;
+/// use tokio_bichannel::{durable, Direction};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("jobs.log");
+/// let jobs = Direction::bounded(10).name("jobs");
+///
+/// let (tx, mut rx) = durable::channel::<String, _>(&path, Json::new(), jobs.clone())
+///     .await
+///     .unwrap();
+/// tx.send("resize image".to_string()).await.unwrap();
+/// tx.send("send email".to_string()).await.unwrap();
+///
+/// let (seq, job) = rx.recv().await.unwrap();
+/// assert_eq!(job, "resize image");
+/// rx.ack(seq).await.unwrap();
+/// drop((tx, rx));
+///
+/// // After a restart, only the unacknowledged job is left.
+/// let (_tx, mut rx) = durable::channel::<String, _>(&path, Json::new(), jobs).await.unwrap();
+/// assert_eq!(rx.recv().await.unwrap().1, "send email");
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+pub async fn channel<T, C>(
+    path: impl AsRef<Path>,
+    mut codec: C,
+    direction: Direction,
+) -> Result<(DurableSender<T, C>, DurableReceiver<T, C>), DurableError>
+where
+    T: Serialize + DeserializeOwned,
+    C: Codec,
+{
+    assert!(
+        direction.is_lossless(),
+        "a durable direction must not drop queued messages"
+    );
+
+    let path = path.as_ref();
+    let lock = lock(path).await?;
+    let mut messages = BTreeMap::new();
+    let mut next_seq = 0;
+
+    match tokio::fs::read(path).await {
+        Ok(bytes) => {
+            let mut buf = BytesMut::from(&bytes[..]);
+            while let Some(record) = codec.decode::<Record<T>>(&mut buf)? {
+                match record {
+                    Record::Header { next_seq: seq } => next_seq = next_seq.max(seq),
+                    Record::Message { seq, value } => {
+                        next_seq = next_seq.max(seq + 1);
+                        messages.insert(seq, value);
+                    }
+                    Record::Ack { seq } => {
+                        messages.remove(&seq);
+                    }
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut unacked = BTreeMap::new();
+    for (seq, value) in &messages {
+        let mut buf = BytesMut::new();
+        codec.encode(&Record::Message { seq: *seq, value }, &mut buf)?;
+        unacked.insert(*seq, buf);
+    }
+
+    let buf = snapshot(&mut codec, next_seq, &unacked)?;
+    let file = rewrite(path, &buf).await?;
+    let log = Arc::new(Mutex::new(Log {
+        path: path.to_owned(),
+        file,
+        codec,
+        next_seq,
+        unacked,
+        len: buf.len() as u64,
+        live: buf.len() as u64,
+        _lock: lock,
+    }));
+    let (sender, receiver) = direction.create_detached();
+
+    Ok((
+        DurableSender {
+            log: log.clone(),
+            sender,
+        },
+        DurableReceiver {
+            log,
+            replay: messages.into_iter().collect(),
+            receiver,
+        },
+    ))
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::remote::Json;
+
+    #[tokio::test]
+    async fn test_resume_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        for i in 0..4 {
+            assert_eq!(tx.send(i as u32 * 10).await.unwrap(), i);
+        }
+        let (a, _) = rx.recv().await.unwrap();
+        let (b, _) = rx.recv().await.unwrap();
+        rx.ack(b).await.unwrap();
+        assert_eq!(rx.unacked().await, 3);
+        drop((tx, rx));
+
+        // Received but unacknowledged messages come back, and sequence numbers carry on.
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        assert_eq!(tx.send(40).await.unwrap(), 4);
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Some((seq, value)) = rx.recv().await {
+            received.push(value);
+            rx.ack(seq).await.unwrap();
+        }
+        assert_eq!(a, 0);
+        assert_eq!(received, vec![0, 20, 30, 40]);
+        drop(rx);
+
+        // Opening compacts away every message, but not the sequence numbers.
+        for _ in 0..2 {
+            let (tx, rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+                .await
+                .unwrap();
+            assert_eq!(rx.unacked().await, 0);
+            drop((tx, rx));
+        }
+        let (tx, _rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        assert_eq!(tx.send(50).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        tx.send(0).await.unwrap();
+        rx.recv().await.unwrap();
+        for i in 1..300 {
+            let seq = tx.send(i).await.unwrap();
+            assert_eq!(rx.recv().await, Some((seq, i)));
+            rx.ack(seq).await.unwrap();
+        }
+
+        // Only the first message is left unacknowledged, so the log stays small.
+        let len = tokio::fs::metadata(&path).await.unwrap().len();
+        assert!(len < 2 * COMPACT_MIN_GARBAGE, "log of {len} bytes");
+        drop((tx, rx));
+
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await, Some((0, 0)));
+        assert_eq!(tx.send(300).await.unwrap(), 300);
+    }
+
+    #[tokio::test]
+    async fn test_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+
+        let (tx, rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        let result = channel::<u32, _>(&path, Json::new(), Direction::bounded(4)).await;
+        assert!(matches!(result, Err(DurableError::Locked)));
+
+        // Released once both halves are gone.
+        drop(tx);
+        let result = channel::<u32, _>(&path, Json::new(), Direction::bounded(4)).await;
+        assert!(matches!(result, Err(DurableError::Locked)));
+        drop(rx);
+        channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_without_receiver() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+
+        let (tx, rx) = channel::<String, _>(&path, Json::new(), Direction::bounded(1))
+            .await
+            .unwrap();
+        drop(rx);
+        for i in 0..3 {
+            tx.send(i.to_string()).await.unwrap();
+        }
+        drop(tx);
+
+        let (_tx, mut rx) = channel::<String, _>(&path, Json::new(), Direction::bounded(1))
+            .await
+            .unwrap();
+        for i in 0..3 {
+            assert_eq!(rx.recv().await.unwrap().1, i.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+        let direction = Direction::unbounded().name("jobs");
+
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), direction)
+            .await
+            .unwrap();
+        assert_eq!(tx.name(), Some("jobs"));
+        assert_eq!(rx.name(), Some("jobs"));
+
+        // Never waits for the receiver.
+        for i in 0..100 {
+            tx.send(i).await.unwrap();
+        }
+        for i in 0..100 {
+            assert_eq!(rx.recv().await, Some((i as u64, i)));
+        }
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "must not drop queued messages")]
+    async fn test_lossy_direction() {
+        let dir = tempfile::tempdir().unwrap();
+        let direction = Direction::bounded(4).overflow(crate::Overflow::EvictOldest);
+
+        let _ = channel::<u32, _>(dir.path().join("queue.log"), Json::new(), direction).await;
+    }
+
+    #[tokio::test]
+    async fn test_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.log");
+
+        let (tx, rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        tx.send(7).await.unwrap();
+        drop((tx, rx));
+
+        // A crash in the middle of appending a record.
+        let mut file = OpenOptions::new().append(true).open(&path).await.unwrap();
+        file.write_all(b"{\"Message\":{\"seq\":1,").await.unwrap();
+        drop(file);
+
+        let (tx, mut rx) = channel::<u32, _>(&path, Json::new(), Direction::bounded(4))
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await, Some((0, 7)));
+        assert_eq!(tx.send(8).await.unwrap(), 1);
+        assert_eq!(rx.recv().await, Some((1, 8)));
+    }
+}
@@ -0,0 +1,350 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::time::{timeout_at, Instant};
+
+use crate::{channel, Channel};
+
+/// A frame exchanged by the ends of a [`KeepaliveChannel`], either a message or a heartbeat.
+///
+/// This only needs to be named when running a keepalive channel over a transport, for example
+/// with [`remote::bridge`] and `KeepaliveFrame<S>` and `KeepaliveFrame<R>` as the message types.
+///
+/// [`remote::bridge`]: crate::remote::bridge
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "remote", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeepaliveFrame<T> {
+    /// A message sent by the peer.
+    Message(T),
+    /// A heartbeat, which only proves that the peer is alive.
+    Heartbeat,
+}
+
+/// Error returned by [`KeepaliveChannel::recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepaliveError {
+    /// Nothing arrived from the peer within the timeout, so it is presumed hung.
+    Unresponsive,
+    /// The peer stopped sending and every queued message has been received.
+    Closed,
+}
+
+impl fmt::Display for KeepaliveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeepaliveError::Unresponsive => write!(f, "peer unresponsive"),
+            KeepaliveError::Closed => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl Error for KeepaliveError {}
+
+/// A bidirectional channel whose ends exchange heartbeats, so that a peer which hangs without
+/// dropping its end can be told apart from one that is merely quiet.
+///
+/// An end sends a heartbeat whenever it has been waiting in [`KeepaliveChannel::recv`] for
+/// `interval` without sending anything. Heartbeats come from the task driving the end rather
+/// than from a background task, so an end that neither sends nor receives for longer than the
+/// peer's `timeout`, because its task is stuck or busy elsewhere, is reported as unresponsive
+/// by the peer's `recv`. An end that is otherwise idle should therefore wait in `recv`.
+/// Heartbeats are never returned by `recv`, and are not sent while the peer still has frames to
+/// receive, so they do not take up capacity meant for messages.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tokio_bichannel::{keepalive_channel, KeepaliveError};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let interval = Duration::from_millis(10);
+/// let timeout = Duration::from_millis(50);
+/// let (mut chan1, mut chan2) = keepalive_channel::<u32, u32>(10, interval, timeout);
+///
+/// tokio::spawn(async move {
+///     let job = chan2.recv().await.unwrap();
+///     // Hangs without dropping the channel.
+///     tokio::time::sleep(Duration::from_secs(3600)).await;
+///     chan2.send(job * 2).await.unwrap();
+/// });
+///
+/// chan1.send(21).await.unwrap();
+/// assert_eq!(chan1.recv().await, Err(KeepaliveError::Unresponsive));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct KeepaliveChannel<S, R> {
+    channel: Channel<KeepaliveFrame<S>, KeepaliveFrame<R>>,
+    interval: Duration,
+    timeout: Duration,
+    last_sent: Mutex<Instant>,
+    last_heard: Instant,
+}
+
+impl<S, R> KeepaliveChannel<S, R> {
+    /// Wraps a channel carrying frames, such as one returned by [`remote::bridge`], in a
+    /// keepalive channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to exchange messages and heartbeats on.
+    /// * `interval` - How long this end waits in `recv` before sending a heartbeat.
+    /// * `timeout` - How long the peer may stay silent before `recv` reports it as unresponsive.
+    ///   It should be a few times the peer's `interval`.
+    ///
+    /// # Returns
+    ///
+    /// * `KeepaliveChannel<S, R>` - Returns the keepalive channel.
+    ///
+    /// [`remote::bridge`]: crate::remote::bridge
+    pub fn new(
+        channel: Channel<KeepaliveFrame<S>, KeepaliveFrame<R>>,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Self {
+        let now = Instant::now();
+
+        Self {
+            channel,
+            interval,
+            timeout,
+            last_sent: Mutex::new(now),
+            last_heard: now,
+        }
+    }
+
+    /// Sends a message to the peer, which also counts as a heartbeat.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    pub async fn send(&self, s: S) -> Result<(), SendError<S>> {
+        self.channel
+            .send(KeepaliveFrame::Message(s))
+            .await
+            .map_err(|SendError(frame)| SendError(message(frame)))?;
+        self.touch();
+        Ok(())
+    }
+
+    /// Attempts to send a message to the peer without waiting, which also counts as a heartbeat.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), TrySendError<S>>` - Returns `Ok(())` if the message was sent, or an error if the channel is full or closed.
+    pub fn try_send(&self, s: S) -> Result<(), TrySendError<S>> {
+        self.channel
+            .try_send(KeepaliveFrame::Message(s))
+            .map_err(|e| match e {
+                TrySendError::Full(frame) => TrySendError::Full(message(frame)),
+                TrySendError::Closed(frame) => TrySendError::Closed(message(frame)),
+            })?;
+        self.touch();
+        Ok(())
+    }
+
+    /// Receives the next message from the peer, sending heartbeats while waiting.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, KeepaliveError>` - Returns the message, or an error if the peer is unresponsive or the channel is closed.
+    ///   After [`KeepaliveError::Unresponsive`], `recv` can be called again in case the peer recovers.
+    pub async fn recv(&mut self) -> Result<R, KeepaliveError> {
+        loop {
+            let unresponsive_at = self.last_heard + self.timeout;
+            let heartbeat_at = self.last_sent() + self.interval;
+
+            match timeout_at(unresponsive_at.min(heartbeat_at), self.channel.recv()).await {
+                Ok(Some(frame)) => {
+                    self.last_heard = Instant::now();
+                    if let KeepaliveFrame::Message(r) = frame {
+                        return Ok(r);
+                    }
+                }
+                Ok(None) => return Err(KeepaliveError::Closed),
+                Err(_) if Instant::now() >= unresponsive_at => {
+                    return Err(KeepaliveError::Unresponsive);
+                }
+                Err(_) => self.heartbeat(),
+            }
+        }
+    }
+
+    /// Returns how long ago anything was last received from the peer.
+    pub fn since_heard(&self) -> Duration {
+        self.last_heard.elapsed()
+    }
+
+    /// Closes the sending half of the channel, so the peer's `recv` returns
+    /// [`KeepaliveError::Closed`] instead of [`KeepaliveError::Unresponsive`].
+    pub fn close_send(&mut self) {
+        self.channel.close_send();
+    }
+
+    /// Returns the underlying channel of frames.
+    pub fn into_inner(self) -> Channel<KeepaliveFrame<S>, KeepaliveFrame<R>> {
+        self.channel
+    }
+
+    fn heartbeat(&self) {
+        // A frame still waiting in the peer's queue proves we are alive once it is received.
+        if self.channel.send_queue_len() == 0 {
+            // A heartbeat that cannot be sent is dropped here and never handed back to the
+            // caller, so every frame that reaches `message` was sent by `send` or `try_send`.
+            let _ = self.channel.try_send(KeepaliveFrame::Heartbeat);
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!("sent heartbeat");
+        }
+
+        self.touch();
+    }
+
+    fn touch(&self) {
+        *self
+            .last_sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    fn last_sent(&self) -> Instant {
+        *self
+            .last_sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Unwraps a frame handed back by a failed `send` or `try_send`, which only ever sends
+/// [`KeepaliveFrame::Message`]; heartbeats are sent and dropped in `heartbeat` alone.
+fn message<T>(frame: KeepaliveFrame<T>) -> T {
+    match frame {
+        KeepaliveFrame::Message(t) => t,
+        KeepaliveFrame::Heartbeat => unreachable!("only messages are handed back by a failed send"),
+    }
+}
+
+/// Creates a bidirectional channel whose ends exchange heartbeats.
+///
+/// # Arguments
+///
+/// * `buffer_size` - The size of the buffer for each direction.
+/// * `interval` - How long an end waits in `recv` before sending a heartbeat.
+/// * `timeout` - How long an end may stay silent before the peer's `recv` reports it as
+///   unresponsive.
+///
+/// # Returns
+///
+/// * `(KeepaliveChannel<T, U>, KeepaliveChannel<U, T>)` - Returns a tuple of two `KeepaliveChannel` instances.
+pub fn keepalive_channel<T, U>(
+    buffer_size: usize,
+    interval: Duration,
+    timeout: Duration,
+) -> (KeepaliveChannel<T, U>, KeepaliveChannel<U, T>) {
+    let (chan1, chan2) = channel(buffer_size);
+
+    (
+        KeepaliveChannel::new(chan1, interval, timeout),
+        KeepaliveChannel::new(chan2, interval, timeout),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_idle_peers_stay_alive() {
+        let (mut chan1, mut chan2) = keepalive_channel::<u32, u32>(1, INTERVAL, TIMEOUT);
+
+        let peer = tokio::spawn(async move {
+            let r = chan2.recv().await;
+            (chan2, r)
+        });
+
+        // Both ends wait much longer than the timeout, exchanging only heartbeats.
+        let result = tokio::time::timeout(TIMEOUT * 4, chan1.recv()).await;
+        assert!(result.is_err());
+
+        chan1.send(1).await.unwrap();
+        let (chan2, r) = peer.await.unwrap();
+        assert_eq!(r, Ok(1));
+
+        // Heartbeats never reach the user.
+        drop(chan2);
+        assert_eq!(chan1.recv().await, Err(KeepaliveError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_hung_peer() {
+        let (mut chan1, chan2) = keepalive_channel::<u32, u32>(1, INTERVAL, TIMEOUT);
+
+        let start = Instant::now();
+        assert_eq!(chan1.recv().await, Err(KeepaliveError::Unresponsive));
+        assert!(start.elapsed() >= TIMEOUT);
+        assert!(chan1.since_heard() >= TIMEOUT);
+
+        // Recovers once the peer shows signs of life.
+        chan2.send(2).await.unwrap();
+        assert_eq!(chan1.recv().await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_peer_hung_while_holding_its_end() {
+        let (mut chan1, mut chan2) = keepalive_channel::<u32, u32>(1, INTERVAL, TIMEOUT);
+
+        let peer = tokio::spawn(async move {
+            let job = chan2.recv().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            chan2.send(job).await.unwrap();
+        });
+
+        chan1.send(1).await.unwrap();
+        let result = tokio::time::timeout(TIMEOUT * 10, chan1.recv()).await;
+        assert_eq!(result, Ok(Err(KeepaliveError::Unresponsive)));
+        peer.abort();
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats_leave_room_for_messages() {
+        let (mut chan1, chan2) = keepalive_channel::<u32, u32>(1, INTERVAL, TIMEOUT);
+
+        assert_eq!(chan1.recv().await, Err(KeepaliveError::Unresponsive));
+
+        // A single heartbeat is queued no matter how long chan1 waited.
+        let inner = chan2.into_inner();
+        assert_eq!(inner.recv_queue_len(), 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_remote() {
+        use crate::remote::{bridge, Json};
+
+        let (a, b) = tokio::io::duplex(1024);
+        let (a, _) = bridge::<KeepaliveFrame<u32>, KeepaliveFrame<u32>, _, _>(a, Json::new(), 4);
+        let (b, _) = bridge::<KeepaliveFrame<u32>, KeepaliveFrame<u32>, _, _>(b, Json::new(), 4);
+        let mut a = KeepaliveChannel::new(a, INTERVAL, TIMEOUT);
+        let b = KeepaliveChannel::new(b, INTERVAL, TIMEOUT);
+
+        b.send(1).await.unwrap();
+        assert_eq!(a.recv().await, Ok(1));
+        assert_eq!(a.recv().await, Err(KeepaliveError::Unresponsive));
+    }
+}
@@ -1,11 +1,53 @@
-use tokio::sync::mpsc::{channel as create_channel, Receiver, Sender};
 use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
 
+mod batch;
+mod blocking;
+mod builder;
+mod dead_letter;
+#[cfg(feature = "durable")]
+pub mod durable;
+#[cfg(feature = "time")]
+mod keepalive;
+mod metrics;
+pub mod mux;
+mod overflow;
+mod permit;
+mod priority;
+#[cfg(feature = "time")]
+pub mod reliable;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod rpc;
+mod split;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(feature = "time")]
+mod time;
+mod unbounded;
+
+pub use batch::Drain;
+pub use blocking::BlockingIter;
+pub use builder::{ChannelBuilder, Direction};
+pub use dead_letter::{DeadLetter, DeadLetterReason};
+#[cfg(feature = "time")]
+pub use keepalive::{keepalive_channel, KeepaliveChannel, KeepaliveError, KeepaliveFrame};
+pub use metrics::{ChannelStats, DirectionStats};
+pub use overflow::Overflow;
+pub use permit::{OwnedPermit, Permit, PermitIterator};
+pub use priority::{priority_channel, PriorityChannel, DEFAULT_STARVATION_LIMIT};
+pub use split::{ChannelReceiver, ChannelSender, ReuniteError};
+#[cfg(feature = "time")]
+pub use time::RecvTimeoutError;
+pub use unbounded::{unbounded_channel, UnboundedChannel};
+
 /// A bidirectional channel structure that supports sending and receiving messages.
 /// 
 /// # Examples
 /// 
 /// ```
+/// # use tokio_bichannel::channel;
+/// # #[tokio::main]
+/// # async fn main() {
 /// let (mut l, mut r) = channel::<String, String>(10);
 /// 
 /// l.send("Hello from chan1".to_string()).await.unwrap();
@@ -13,16 +55,20 @@ use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
 /// 
 /// assert_eq!(r.recv().await.unwrap(), "Hello from chan1");
 /// assert_eq!(l.recv().await.unwrap(), "Hello from chan2");
+/// # }
 /// ```
 #[derive(Debug)]
 pub struct Channel<S, R> {
-    sender: Sender<S>,
-    receiver: Receiver<R>,
+    sender: ChannelSender<S>,
+    receiver: ChannelReceiver<R>,
 }
 
 impl<S, R> Channel<S, R> {
     /// Sends a message through the channel.
     /// 
+    /// If the peer stopped receiving and a dead-letter sink was set with
+    /// [`Channel::set_dead_letter_sink`], the message goes to the sink and this returns `Ok(())`.
+    /// 
     /// # Arguments
     /// 
     /// * `s` - The message to send.
@@ -30,11 +76,16 @@ impl<S, R> Channel<S, R> {
     /// # Returns
     /// 
     /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    ///   A message rejected by [`Overflow::Reject`] is handed back the same way;
+    ///   [`Channel::try_send`] tells the two cases apart.
     /// 
     /// # Examples
     /// 
     /// ```
+    /// # let (channel, _peer) = tokio_bichannel::channel::<String, String>(10);
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// channel.send("Hello".to_string()).await.unwrap();
+    /// # });
     /// ```
     pub async fn send(&self, s: S) -> Result<(), SendError<S>> {
         self.sender.send(s).await
@@ -49,9 +100,12 @@ impl<S, R> Channel<S, R> {
     /// # Examples
     /// 
     /// ```
+    /// # let (mut channel, _) = tokio_bichannel::channel::<String, String>(10);
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// while let Some(msg) = channel.recv().await {
     ///     println!("Received: {}", msg);
     /// }
+    /// # });
     /// ```
     pub async fn recv(&mut self) -> Option<R> {
         self.receiver.recv().await
@@ -59,6 +113,9 @@ impl<S, R> Channel<S, R> {
 
     /// Attempts to send a message through the channel without blocking.
     /// 
+    /// If the peer stopped receiving and a dead-letter sink was set with
+    /// [`Channel::set_dead_letter_sink`], the message goes to the sink and this returns `Ok(())`.
+    /// 
     /// # Arguments
     /// 
     /// * `s` - The message to send.
@@ -70,6 +127,7 @@ impl<S, R> Channel<S, R> {
     /// # Examples
     /// 
     /// ```
+    /// # let (channel, _peer) = tokio_bichannel::channel::<String, String>(10);
     /// match channel.try_send("Hello".to_string()) {
     ///    Ok(_) => println!("Message sent"),
     ///    Err(e) => println!("Error: {:?}", e),
@@ -87,6 +145,7 @@ impl<S, R> Channel<S, R> {
     /// 
     /// # Examples
     /// ```
+    /// # let (mut channel, _peer) = tokio_bichannel::channel::<String, String>(10);
     /// match channel.try_recv() {
     ///     Ok(msg) => println!("Received: {}", msg),
     ///     Err(e) => println!("Error: {:?}", e),
@@ -95,10 +154,152 @@ impl<S, R> Channel<S, R> {
     pub fn try_recv(&mut self) -> Result<R, TryRecvError> {
         self.receiver.try_recv()
     }
+
+    /// Stops sending to the peer while still allowing messages to be received from it.
+    /// 
+    /// The peer receives the messages that are still queued, after which its `recv` returns `None`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (mut chan1, mut chan2) = channel::<String, String>(10);
+    /// 
+    /// chan1.send("last message".to_string()).await.unwrap();
+    /// chan1.close_send();
+    /// 
+    /// assert_eq!(chan2.recv().await.unwrap(), "last message");
+    /// assert!(chan2.recv().await.is_none());
+    /// 
+    /// chan2.send("reply".to_string()).await.unwrap();
+    /// assert_eq!(chan1.recv().await.unwrap(), "reply");
+    /// # }
+    /// ```
+    pub fn close_send(&mut self) {
+        self.sender.close();
+    }
+
+    /// Stops receiving from the peer while still allowing messages to be sent to it.
+    /// 
+    /// Messages that are already queued can still be received, but the peer's sends fail.
+    pub fn close_recv(&mut self) {
+        self.receiver.close();
+    }
+
+    /// Returns whether messages can no longer be sent, either because of [`Channel::close_send`]
+    /// or because the peer stopped receiving.
+    pub fn is_send_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Returns whether the peer can no longer send, either because of [`Channel::close_recv`]
+    /// or because the peer stopped sending.
+    /// 
+    /// Messages may still be queued even if this returns `true`.
+    pub fn is_recv_closed(&self) -> bool {
+        self.receiver.is_closed()
+    }
+
+    /// Waits until the peer can no longer receive messages, for example because it was dropped.
+    /// 
+    /// This completes without consuming any messages, so it can be used to notice a dead peer even
+    /// when there is nothing to send, or after [`Channel::close_send`].
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, chan2) = channel::<String, String>(10);
+    /// 
+    /// tokio::spawn(async move {
+    ///     drop(chan2);
+    /// });
+    /// 
+    /// chan1.closed().await;
+    /// assert!(chan1.is_closed());
+    /// # }
+    /// ```
+    pub async fn closed(&self) {
+        self.sender.peer_closed().await
+    }
+
+    /// Returns whether the peer can no longer receive messages. This is the non-waiting
+    /// counterpart of [`Channel::closed`].
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_peer_closed()
+    }
+
+    /// Returns the number of messages that can currently be sent without waiting, or `None` if
+    /// the sending direction is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.sender.capacity()
+    }
+
+    /// Returns the capacity of the sending direction, or `None` if it is unbounded.
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.sender.max_capacity()
+    }
+
+    /// Returns the number of messages sent that the peer has yet to receive, including
+    /// slots reserved for messages that are about to be sent.
+    pub fn send_queue_len(&self) -> usize {
+        self.sender.queue_len()
+    }
+
+    /// Returns the number of messages waiting to be received.
+    pub fn recv_queue_len(&self) -> usize {
+        self.receiver.queue_len()
+    }
+
+    /// Returns whether both channels send to the same peer through the same direction.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other` - The channel to compare against.
+    pub fn same_channel(&self, other: &Self) -> bool {
+        self.sender.same_channel(&other.sender)
+    }
+
+    /// Splits the channel into a sending half and a receiving half that can be owned by
+    /// different tasks.
+    ///
+    /// The halves can be put back together with [`ChannelSender::reunite`] or
+    /// [`ChannelReceiver::reunite`].
+    ///
+    /// # Returns
+    ///
+    /// * `(ChannelSender<S>, ChannelReceiver<R>)` - Returns the sending and receiving halves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = channel::<String, String>(10);
+    /// let (tx, mut rx) = chan1.split();
+    ///
+    /// let reader = tokio::spawn(async move { rx.recv().await });
+    /// tx.send("Hello from chan1".to_string()).await.unwrap();
+    ///
+    /// assert_eq!(chan2.recv().await.unwrap(), "Hello from chan1");
+    /// chan2.send("Hello from chan2".to_string()).await.unwrap();
+    /// assert_eq!(reader.await.unwrap().unwrap(), "Hello from chan2");
+    /// # }
+    /// ```
+    pub fn split(self) -> (ChannelSender<S>, ChannelReceiver<R>) {
+        (self.sender, self.receiver)
+    }
 }
 
 /// Creates a bidirectional channel with the specified buffer size.
 /// 
+/// Use [`ChannelBuilder`] to configure each direction independently.
+/// 
 /// # Arguments
 /// 
 /// * `buffer_size` - The size of the buffer for the channel.
@@ -127,19 +328,7 @@ impl<S, R> Channel<S, R> {
 /// }
 /// ```
 pub fn channel<T, U>(buffer_size: usize) -> (Channel<T, U>, Channel<U, T>) {
-    let (ls, lr) = create_channel(buffer_size);
-    let (rs, rr) = create_channel(buffer_size);
-
-    (
-        Channel {
-            sender: ls,
-            receiver: rr,
-        },
-        Channel {
-            sender: rs,
-            receiver: lr,
-        },
-    )
+    ChannelBuilder::new(buffer_size).build()
 }
 
 #[cfg(test)]
@@ -164,7 +353,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_try_recv() {
-        let (mut chan1, mut chan2) = channel::<String, String>(10);
+        let (chan1, mut chan2) = channel::<String, String>(10);
 
         chan1.send("Hello from chan1".to_string()).await.unwrap();
 
@@ -194,4 +383,101 @@ mod tests {
 
         handle.join().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let (chan1, chan2) = channel::<u32, u32>(10);
+        assert!(!chan1.is_closed());
+
+        let supervisor = tokio::spawn(async move {
+            chan1.closed().await;
+            chan1.is_closed()
+        });
+
+        drop(chan2);
+        assert!(supervisor.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_introspection() {
+        let (mut chan1, chan2) = ChannelBuilder::new(4)
+            .right_to_left(Direction::bounded(8))
+            .build::<u32, u32>();
+
+        assert_eq!(chan1.max_capacity(), Some(4));
+        assert_eq!(chan2.max_capacity(), Some(8));
+
+        chan1.send(1).await.unwrap();
+        chan1.send(2).await.unwrap();
+        chan2.send(3).await.unwrap();
+
+        assert_eq!(chan1.capacity(), Some(2));
+        assert_eq!(chan1.send_queue_len(), 2);
+        assert_eq!(chan2.recv_queue_len(), 2);
+        assert_eq!(chan1.recv_queue_len(), 1);
+
+        chan1.recv().await.unwrap();
+        assert_eq!(chan1.recv_queue_len(), 0);
+        assert_eq!(chan2.send_queue_len(), 0);
+
+        let (other, _) = channel::<u32, u32>(4);
+        assert!(chan1.same_channel(&chan1));
+        assert!(!chan1.same_channel(&other));
+    }
+
+    #[tokio::test]
+    async fn test_half_close() {
+        let (mut chan1, mut chan2) = channel::<u32, u32>(10);
+
+        chan1.send(1).await.unwrap();
+        chan1.close_send();
+
+        assert!(chan1.is_send_closed());
+        assert!(!chan1.is_recv_closed());
+        assert_eq!(chan1.send(2).await.unwrap_err().0, 2);
+        assert!(chan2.is_recv_closed());
+
+        assert_eq!(chan2.recv().await, Some(1));
+        assert_eq!(chan2.recv().await, None);
+
+        chan2.send(3).await.unwrap();
+        assert_eq!(chan1.recv().await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_close_recv() {
+        let (mut chan1, mut chan2) = channel::<u32, u32>(10);
+
+        chan2.send(1).await.unwrap();
+        chan1.close_recv();
+
+        assert!(chan1.is_recv_closed());
+        assert!(chan2.is_send_closed());
+        assert!(chan2.send(2).await.is_err());
+        assert_eq!(chan1.recv().await, Some(1));
+        assert_eq!(chan1.recv().await, None);
+
+        chan1.send(3).await.unwrap();
+        assert_eq!(chan2.recv().await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_closed_after_close_send() {
+        let (mut chan1, chan2) = channel::<u32, u32>(10);
+        chan1.close_send();
+
+        assert!(chan1.is_send_closed());
+        assert!(!chan1.is_closed());
+
+        let supervisor = tokio::spawn(async move {
+            chan1.closed().await;
+            chan1.is_closed()
+        });
+
+        tokio::task::yield_now().await;
+        assert!(!supervisor.is_finished());
+
+        drop(chan2);
+        assert!(supervisor.await.unwrap());
+    }
 }
@@ -0,0 +1,261 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::split::DirectionState;
+use crate::{Channel, ChannelReceiver, ChannelSender};
+
+/// A message queued in a direction, stamped with the time it was sent if the direction records
+/// metrics or has a time to live.
+#[derive(Debug)]
+pub(crate) struct Envelope<T> {
+    pub(crate) value: T,
+    sent_at: Option<Instant>,
+}
+
+impl<T> Envelope<T> {
+    pub(crate) fn new(value: T, state: &DirectionState<T>) -> Self {
+        let stamped = state.metrics.is_some() || state.ttl.is_some();
+
+        Self {
+            value,
+            sent_at: stamped.then(Instant::now),
+        }
+    }
+
+    /// Returns whether the message was queued for longer than `ttl`.
+    pub(crate) fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        match (self.sent_at, ttl) {
+            (Some(sent_at), Some(ttl)) => sent_at.elapsed() > ttl,
+            _ => false,
+        }
+    }
+}
+
+/// The counters of a direction, shared by its sending and receiving halves.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    sent: AtomicU64,
+    received: AtomicU64,
+    send_blocked: AtomicU64,
+    total_latency: AtomicU64,
+    max_latency: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn record_sent(&self, n: u64) {
+        self.sent.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_blocked(&self, since: Instant) {
+        self.send_blocked
+            .fetch_add(nanos(since.elapsed()), Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received<T>(&self, envelope: &Envelope<T>) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(sent_at) = envelope.sent_at {
+            let latency = nanos(sent_at.elapsed());
+            self.total_latency.fetch_add(latency, Ordering::Relaxed);
+            self.max_latency.fetch_max(latency, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> DirectionState<T> {
+    /// Returns how many messages were discarded by the overflow policy of the direction.
+    pub(crate) fn discarded(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed) + self.evicted.load(Ordering::Relaxed)
+    }
+
+    fn snapshot(&self) -> Option<DirectionStats> {
+        let metrics = self.metrics.as_ref()?;
+        let sent = metrics.sent.load(Ordering::Relaxed);
+        let received = metrics.received.load(Ordering::Relaxed);
+        // Evicted and expired messages were sent but will never be received.
+        let evicted = self.evicted.load(Ordering::Relaxed);
+        let expired = self.expired.load(Ordering::Relaxed);
+
+        Some(DirectionStats {
+            sent,
+            received,
+            queue_depth: sent.saturating_sub(received + evicted + expired),
+            discarded: self.discarded(),
+            expired,
+            send_blocked: Duration::from_nanos(metrics.send_blocked.load(Ordering::Relaxed)),
+            total_latency: Duration::from_nanos(metrics.total_latency.load(Ordering::Relaxed)),
+            max_latency: Duration::from_nanos(metrics.max_latency.load(Ordering::Relaxed)),
+        })
+    }
+}
+
+fn nanos(d: Duration) -> u64 {
+    u64::try_from(d.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// A snapshot of the activity of one direction of a channel.
+///
+/// Metrics are only recorded for directions created with [`Direction::metrics`].
+///
+/// [`Direction::metrics`]: crate::Direction::metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectionStats {
+    /// The number of messages sent on the direction.
+    pub sent: u64,
+    /// The number of messages received from the direction.
+    pub received: u64,
+    /// The number of messages sent but not yet received.
+    pub queue_depth: u64,
+    /// The number of messages discarded by the [`Overflow`] policy of the direction.
+    ///
+    /// [`Overflow`]: crate::Overflow
+    pub discarded: u64,
+    /// The number of messages that outlived the time to live of the direction before being
+    /// received.
+    pub expired: u64,
+    /// The total time senders spent waiting for capacity.
+    pub send_blocked: Duration,
+    /// The total time received messages spent queued, from being sent to being received.
+    pub total_latency: Duration,
+    /// The longest time a received message spent queued.
+    pub max_latency: Duration,
+}
+
+impl DirectionStats {
+    /// Returns the average time a received message spent queued, or `None` if nothing was
+    /// received yet.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let received = u32::try_from(self.received).unwrap_or(u32::MAX);
+        (received > 0).then(|| self.total_latency / received)
+    }
+}
+
+/// A snapshot of the activity of both directions of a [`Channel`], returned by
+/// [`Channel::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// The direction the channel sends on, if it records metrics.
+    pub outgoing: Option<DirectionStats>,
+    /// The direction the channel receives from, if it records metrics.
+    pub incoming: Option<DirectionStats>,
+}
+
+impl<S> ChannelSender<S> {
+    /// Returns a snapshot of the metrics of the direction this half sends on.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DirectionStats>` - Returns the metrics, or `None` if the direction does not record them.
+    pub fn stats(&self) -> Option<DirectionStats> {
+        self.state.snapshot()
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Returns a snapshot of the metrics of the direction this half receives from.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DirectionStats>` - Returns the metrics, or `None` if the direction does not record them.
+    pub fn stats(&self) -> Option<DirectionStats> {
+        self.state.snapshot()
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Returns a snapshot of the metrics of both directions of the channel.
+    ///
+    /// The snapshot is cheap to take and can be polled periodically by monitoring.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::ChannelBuilder;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = ChannelBuilder::new(10).metrics().build::<u32, u32>();
+    ///
+    /// chan1.send(1).await.unwrap();
+    /// chan1.send(2).await.unwrap();
+    /// chan2.recv().await.unwrap();
+    ///
+    /// let stats = chan1.stats().outgoing.unwrap();
+    /// assert_eq!(stats.sent, 2);
+    /// assert_eq!(stats.received, 1);
+    /// assert_eq!(stats.queue_depth, 1);
+    /// # }
+    /// ```
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            outgoing: self.sender.stats(),
+            incoming: self.receiver.stats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{channel, ChannelBuilder, Direction};
+
+    #[tokio::test]
+    async fn test_disabled_by_default() {
+        let (chan1, chan2) = channel::<u32, u32>(1);
+        chan1.send(1).await.unwrap();
+
+        assert_eq!(chan1.stats().outgoing, None);
+        assert_eq!(chan2.stats().incoming, None);
+    }
+
+    #[tokio::test]
+    async fn test_counts_and_depth() {
+        let (chan1, mut chan2) = ChannelBuilder::new(16)
+            .left_to_right(Direction::bounded(16).metrics())
+            .build::<u32, u32>();
+
+        chan1.send(0).await.unwrap();
+        chan1.try_send(1).unwrap();
+        chan1.reserve().await.unwrap().send(2);
+        chan1.send_all(3..10).await.unwrap();
+
+        let mut buffer = Vec::new();
+        chan2.recv().await.unwrap();
+        chan2.try_recv().unwrap();
+        chan2.recv_many(&mut buffer, 3).await;
+
+        let stats = chan2.stats().incoming.unwrap();
+        assert_eq!(stats.sent, 10);
+        assert_eq!(stats.received, 5);
+        assert_eq!(stats.queue_depth, 5);
+        assert_eq!(chan1.stats().outgoing, Some(stats));
+
+        // Only the left-to-right direction records metrics.
+        assert_eq!(chan1.stats().incoming, None);
+        assert_eq!(chan2.stats().outgoing, None);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_and_latency() {
+        let (chan1, mut chan2) = ChannelBuilder::new(1).metrics().build::<u32, u32>();
+
+        chan1.send(1).await.unwrap();
+
+        let receiver = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            chan2.recv().await.unwrap();
+            chan2.recv().await.unwrap();
+            chan2
+        });
+
+        // Waits until the receiver makes room.
+        chan1.send(2).await.unwrap();
+        let chan2 = receiver.await.unwrap();
+
+        let stats = chan2.stats().incoming.unwrap();
+        assert_eq!(stats.received, 2);
+        assert!(stats.send_blocked >= Duration::from_millis(20));
+        assert!(stats.max_latency >= Duration::from_millis(20));
+        assert!(stats.mean_latency().unwrap() <= stats.max_latency);
+    }
+}
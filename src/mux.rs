@@ -0,0 +1,785 @@
+//! Running many independent streams over a single [`Channel`].
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Poll;
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Semaphore;
+
+use crate::split::WeakChannelSender;
+use crate::{channel as bichannel, Channel, ChannelReceiver, ChannelSender};
+
+/// A segment of a stream as it travels between two [`Multiplexer`]s.
+///
+/// The low bit of a stream ID tells whether the end sending the segment opened the stream
+/// (`0`) or accepted it (`1`), so both ends can open streams without agreeing on IDs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "remote", derive(serde::Serialize, serde::Deserialize))]
+pub enum Segment<T> {
+    /// Opens a new stream.
+    Open {
+        /// The ID of the stream.
+        stream: u64,
+    },
+    /// A message sent on a stream.
+    Data {
+        /// The ID of the stream.
+        stream: u64,
+        /// The message.
+        value: T,
+    },
+    /// Allows the receiver of the segment to send `n` more messages on a stream.
+    Credit {
+        /// The ID of the stream.
+        stream: u64,
+        /// The number of messages granted.
+        n: usize,
+    },
+    /// The sender of the segment will send no more messages on a stream.
+    Close {
+        /// The ID of the stream.
+        stream: u64,
+    },
+    /// The sender of the segment will receive no more messages on a stream.
+    Stop {
+        /// The ID of the stream.
+        stream: u64,
+    },
+}
+
+/// Error returned by [`Multiplexer::open_stream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuxError {
+    /// The underlying channel is closed, so no stream can be opened.
+    Closed,
+}
+
+impl fmt::Display for MuxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MuxError::Closed => write!(f, "multiplexed channel closed"),
+        }
+    }
+}
+
+impl Error for MuxError {}
+
+#[derive(Debug)]
+struct Route<R> {
+    // Dropped once the peer closes its sending side of the stream.
+    inbox: Option<UnboundedSender<R>>,
+    // How many more messages the peer lets us send.
+    credit: Arc<Semaphore>,
+    // How many more messages we let the peer send.
+    allowance: Arc<AtomicUsize>,
+    closed_send: bool,
+}
+
+#[derive(Debug)]
+struct Routes<R> {
+    streams: HashMap<u64, Route<R>>,
+    closed: bool,
+}
+
+type Shared<R> = Arc<Mutex<Routes<R>>>;
+
+impl<R> Routes<R> {
+    /// Hands a message from the peer to its stream, if the peer had credit left to send it.
+    ///
+    /// Returns `false` if the peer overran its window, in which case the stream stops
+    /// receiving from it.
+    fn deliver(&mut self, key: u64, value: R) -> bool {
+        let Some(route) = self.streams.get_mut(&key) else {
+            return true;
+        };
+        let Some(inbox) = route.inbox.as_ref() else {
+            return true;
+        };
+
+        let granted = route
+            .allowance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok();
+        if granted {
+            let _ = inbox.send(value);
+            return true;
+        }
+
+        route.inbox = None;
+        if route.closed_send {
+            self.streams.remove(&key);
+        }
+        false
+    }
+}
+
+/// One end of a multiplexed channel, opening and accepting streams over a single [`Channel`].
+///
+/// Every stream is a [`Channel`] of its own, with the message types of the multiplexer. Each
+/// direction of a stream has its own flow control: the sender may have at most `window`
+/// messages on their way to the receiving end, on top of up to `window` more waiting in the
+/// buffer of the receiving stream, so a stream that is not read never holds up the others. A
+/// peer sending more than that is cut off: the stream stops receiving from it, as if it had
+/// been dropped.
+///
+/// Dropping a stream, or calling [`Channel::close_send`] on it, makes the peer's stream
+/// return `None` once it has received every message sent before. Dropping or closing the
+/// receiving half of a stream makes the peer's `send` on it fail.
+///
+/// Streams stay usable after the multiplexer itself is dropped. They all close when the
+/// underlying channel does, and the tasks routing their segments end once both ends have
+/// dropped their multiplexer and every stream.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (client, mut server) = tokio_bichannel::mux::channel::<String, String>(16, 4);
+///
+/// tokio::spawn(async move {
+///     while let Some(mut stream) = server.accept_stream().await {
+///         tokio::spawn(async move {
+///             while let Some(msg) = stream.recv().await {
+///                 stream.send(msg.to_uppercase()).await.unwrap();
+///             }
+///         });
+///     }
+/// });
+///
+/// let mut a = client.open_stream().await.unwrap();
+/// let mut b = client.open_stream().await.unwrap();
+///
+/// b.send("world".to_string()).await.unwrap();
+/// a.send("hello".to_string()).await.unwrap();
+///
+/// assert_eq!(a.recv().await.unwrap(), "HELLO");
+/// assert_eq!(b.recv().await.unwrap(), "WORLD");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Multiplexer<S, R> {
+    transport: ChannelSender<Segment<S>>,
+    routes: Shared<R>,
+    accepted: UnboundedReceiver<Channel<S, R>>,
+    next_id: AtomicU64,
+    window: usize,
+}
+
+impl<S, R> Multiplexer<S, R>
+where
+    S: Send + 'static,
+    R: Send + 'static,
+{
+    /// Wraps a channel whose peer is (or will be wrapped by) a [`Multiplexer`], such as one
+    /// returned by [`remote::bridge`].
+    ///
+    /// This spawns a task that routes segments to their streams, so it must be called from
+    /// within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to run the streams over.
+    /// * `window` - How many messages the peer may have on their way on each stream before
+    ///   this end takes them. This is also the buffer size of each stream, so up to twice as
+    ///   many can be waiting to be received.
+    ///
+    /// # Returns
+    ///
+    /// * `Multiplexer<S, R>` - Returns the multiplexer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    ///
+    /// [`remote::bridge`]: crate::remote::bridge
+    pub fn new(channel: Channel<Segment<S>, Segment<R>>, window: usize) -> Self {
+        assert!(window > 0, "the stream window must be at least one message");
+
+        let (transport, receiver) = channel.split();
+        let routes = Arc::new(Mutex::new(Routes {
+            streams: HashMap::new(),
+            closed: false,
+        }));
+        let (accept, accepted) = unbounded_channel();
+
+        tokio::spawn(dispatch(
+            receiver,
+            transport.downgrade(),
+            routes.clone(),
+            accept,
+            window,
+        ));
+
+        Self {
+            transport,
+            routes,
+            accepted,
+            next_id: AtomicU64::new(0),
+            window,
+        }
+    }
+
+    /// Opens a new stream, which the peer receives from [`Multiplexer::accept_stream`].
+    ///
+    /// Messages can be sent on the stream right away, and are delivered once the peer grants
+    /// the first window.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Channel<S, R>, MuxError>` - Returns the stream, or an error if the underlying channel is closed.
+    pub async fn open_stream(&self) -> Result<Channel<S, R>, MuxError> {
+        let key = self.next_id.fetch_add(1, Ordering::Relaxed) << 1;
+        let credit = Arc::new(Semaphore::new(0));
+        let allowance = Arc::new(AtomicUsize::new(0));
+        let (inbox, inbox_rx) = unbounded_channel();
+
+        {
+            let mut routes = self.routes.lock().unwrap_or_else(PoisonError::into_inner);
+            if routes.closed {
+                return Err(MuxError::Closed);
+            }
+            routes.streams.insert(
+                key,
+                Route {
+                    inbox: Some(inbox),
+                    credit: credit.clone(),
+                    allowance: allowance.clone(),
+                    closed_send: false,
+                },
+            );
+        }
+
+        if self
+            .transport
+            .send(Segment::Open { stream: key })
+            .await
+            .is_err()
+        {
+            self.routes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .streams
+                .remove(&key);
+            return Err(MuxError::Closed);
+        }
+
+        Ok(start_stream(
+            key,
+            inbox_rx,
+            credit,
+            allowance,
+            &self.transport,
+            &self.routes,
+            self.window,
+        ))
+    }
+
+    /// Receives the next stream opened by the peer.
+    ///
+    /// Streams opened by the peer are queued until they are accepted, and the peer may send up
+    /// to `window` messages on each meanwhile.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Channel<S, R>>` - Returns the stream, or `None` if the underlying channel is closed.
+    pub async fn accept_stream(&mut self) -> Option<Channel<S, R>> {
+        self.accepted.recv().await
+    }
+
+    /// Returns the number of streams with at least one direction still open.
+    pub fn stream_count(&self) -> usize {
+        self.routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .streams
+            .len()
+    }
+}
+
+/// Creates the local end of a stream and spawns the tasks moving its messages.
+fn start_stream<S, R>(
+    key: u64,
+    inbox: UnboundedReceiver<R>,
+    credit: Arc<Semaphore>,
+    allowance: Arc<AtomicUsize>,
+    transport: &ChannelSender<Segment<S>>,
+    routes: &Shared<R>,
+    window: usize,
+) -> Channel<S, R>
+where
+    S: Send + 'static,
+    R: Send + 'static,
+{
+    let (stream, local) = bichannel::<S, R>(window);
+    let (to_user, from_user) = local.split();
+
+    tokio::spawn(forward_outgoing(
+        key,
+        from_user,
+        credit,
+        transport.clone(),
+        routes.clone(),
+    ));
+    tokio::spawn(forward_incoming(
+        key,
+        inbox,
+        allowance,
+        to_user,
+        transport.clone(),
+        window,
+    ));
+
+    stream
+}
+
+async fn forward_outgoing<S, R>(
+    key: u64,
+    mut from_user: ChannelReceiver<S>,
+    credit: Arc<Semaphore>,
+    transport: ChannelSender<Segment<S>>,
+    routes: Shared<R>,
+) {
+    loop {
+        // Fails once the peer stops receiving or the underlying channel closes.
+        let Ok(permit) = credit.acquire().await else {
+            from_user.close();
+            let _ = transport.send(Segment::Close { stream: key }).await;
+            break;
+        };
+
+        let Some(value) = from_user.recv().await else {
+            let _ = transport.send(Segment::Close { stream: key }).await;
+            break;
+        };
+        permit.forget();
+
+        if transport
+            .send(Segment::Data { stream: key, value })
+            .await
+            .is_err()
+        {
+            from_user.close();
+            break;
+        }
+    }
+
+    let mut routes = routes.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(route) = routes.streams.get_mut(&key) {
+        route.closed_send = true;
+        if route.inbox.is_none() {
+            routes.streams.remove(&key);
+        }
+    }
+}
+
+async fn forward_incoming<S, R>(
+    key: u64,
+    mut inbox: UnboundedReceiver<R>,
+    allowance: Arc<AtomicUsize>,
+    to_user: ChannelSender<R>,
+    transport: ChannelSender<Segment<S>>,
+    window: usize,
+) {
+    // Credit is counted before it is granted, so the peer can never spend it early.
+    allowance.fetch_add(window, Ordering::AcqRel);
+    let _ = transport
+        .send(Segment::Credit {
+            stream: key,
+            n: window,
+        })
+        .await;
+
+    // Credit is handed back in batches to keep the control traffic down.
+    let batch = window.div_ceil(2);
+    let mut taken = 0;
+    let mut stopped = false;
+
+    let mut user_gone = pin!(to_user.closed());
+    loop {
+        // Also wakes up once the stream is dropped, so the peer stops sending even while the
+        // inbox is empty, rather than waiting for credit that never comes.
+        let next = poll_fn(|cx| {
+            if !stopped && user_gone.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            inbox.poll_recv(cx).map(Some)
+        })
+        .await;
+
+        let value = match next {
+            Some(Some(value)) => value,
+            Some(None) => break,
+            None => {
+                stopped = true;
+                let _ = transport.send(Segment::Stop { stream: key }).await;
+                continue;
+            }
+        };
+        if stopped {
+            continue;
+        }
+
+        if to_user.send(value).await.is_err() {
+            stopped = true;
+            let _ = transport.send(Segment::Stop { stream: key }).await;
+            continue;
+        }
+
+        taken += 1;
+        if taken >= batch {
+            allowance.fetch_add(taken, Ordering::AcqRel);
+            let _ = transport
+                .send(Segment::Credit {
+                    stream: key,
+                    n: taken,
+                })
+                .await;
+            taken = 0;
+        }
+    }
+}
+
+async fn dispatch<S, R>(
+    mut receiver: ChannelReceiver<Segment<R>>,
+    transport: WeakChannelSender<Segment<S>>,
+    routes: Shared<R>,
+    accept: UnboundedSender<Channel<S, R>>,
+    window: usize,
+) where
+    S: Send + 'static,
+    R: Send + 'static,
+{
+    while let Some(segment) = receiver.recv().await {
+        // Only the multiplexer and its streams keep the channel to the peer open, so that both
+        // ends shut down once they are all gone; segments for them are of no use after that.
+        let Some(transport) = transport.upgrade() else {
+            break;
+        };
+
+        // The stream whose peer overran its window, to be stopped once the lock is released.
+        let overran = {
+            let mut guard = routes.lock().unwrap_or_else(PoisonError::into_inner);
+
+            match segment {
+                Segment::Open { stream } => {
+                    let key = stream ^ 1;
+                    // The peer may only open streams with its own IDs, and only once.
+                    if stream & 1 == 1 || guard.streams.contains_key(&key) {
+                        continue;
+                    }
+
+                    let credit = Arc::new(Semaphore::new(0));
+                    let allowance = Arc::new(AtomicUsize::new(0));
+                    let (inbox, inbox_rx) = unbounded_channel();
+                    guard.streams.insert(
+                        key,
+                        Route {
+                            inbox: Some(inbox),
+                            credit: credit.clone(),
+                            allowance: allowance.clone(),
+                            closed_send: false,
+                        },
+                    );
+                    drop(guard);
+
+                    let stream = start_stream(
+                        key, inbox_rx, credit, allowance, &transport, &routes, window,
+                    );
+                    let _ = accept.send(stream);
+                    None
+                }
+                Segment::Data { stream, value } => {
+                    let key = stream ^ 1;
+                    (!guard.deliver(key, value)).then_some(key)
+                }
+                Segment::Credit { stream, n } => {
+                    if let Some(route) = guard.streams.get(&(stream ^ 1)) {
+                        route.credit.add_permits(n);
+                    }
+                    None
+                }
+                Segment::Close { stream } => {
+                    let key = stream ^ 1;
+                    if let Some(route) = guard.streams.get_mut(&key) {
+                        route.inbox = None;
+                        if route.closed_send {
+                            guard.streams.remove(&key);
+                        }
+                    }
+                    None
+                }
+                Segment::Stop { stream } => {
+                    if let Some(route) = guard.streams.get(&(stream ^ 1)) {
+                        route.credit.close();
+                    }
+                    None
+                }
+            }
+        };
+
+        // Sent from a task of its own, as waiting here for room in the channel could deadlock
+        // with the peer's dispatch waiting for room in the other direction.
+        if let Some(key) = overran {
+            tokio::spawn(async move {
+                let _ = transport.send(Segment::Stop { stream: key }).await;
+            });
+        }
+    }
+
+    let mut guard = routes.lock().unwrap_or_else(PoisonError::into_inner);
+    guard.closed = true;
+    for route in guard.streams.values_mut() {
+        route.inbox = None;
+        route.credit.close();
+    }
+}
+
+/// Creates a multiplexed channel with the specified buffer size and stream window.
+///
+/// This spawns tasks that route segments to their streams, so it must be called from within a
+/// Tokio runtime.
+///
+/// # Arguments
+///
+/// * `buffer_size` - The size of the buffer for each direction of the underlying channel.
+/// * `window` - How many messages may be on their way on each direction of each stream, not
+///   counting the ones waiting in the receiving stream's buffer.
+///
+/// # Returns
+///
+/// * `(Multiplexer<T, U>, Multiplexer<U, T>)` - Returns the two ends.
+pub fn channel<T, U>(buffer_size: usize, window: usize) -> (Multiplexer<T, U>, Multiplexer<U, T>)
+where
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    let (chan1, chan2) = bichannel(buffer_size);
+    (
+        Multiplexer::new(chan1, window),
+        Multiplexer::new(chan2, window),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_both_sides_open() {
+        let (mut mux1, mut mux2) = channel::<u32, u32>(4, 2);
+
+        let mut a = mux1.open_stream().await.unwrap();
+        let mut b = mux2.open_stream().await.unwrap();
+        let mut a2 = mux2.accept_stream().await.unwrap();
+        let mut b1 = mux1.accept_stream().await.unwrap();
+
+        a.send(1).await.unwrap();
+        b.send(2).await.unwrap();
+        a2.send(3).await.unwrap();
+        b1.send(4).await.unwrap();
+
+        assert_eq!(a2.recv().await, Some(1));
+        assert_eq!(b1.recv().await, Some(2));
+        assert_eq!(a.recv().await, Some(3));
+        assert_eq!(b.recv().await, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_per_stream_flow_control() {
+        let (mux1, mut mux2) = channel::<u32, u32>(4, 2);
+
+        let stalled = mux1.open_stream().await.unwrap();
+        let busy = mux1.open_stream().await.unwrap();
+        let _stalled = mux2.accept_stream().await.unwrap();
+        let mut busy2 = mux2.accept_stream().await.unwrap();
+
+        // Nobody reads the stalled stream, so its sends eventually wait for credit.
+        let result = tokio::time::timeout(Duration::from_millis(50), async {
+            for i in 0.. {
+                stalled.send(i).await.unwrap();
+            }
+        })
+        .await;
+        assert!(result.is_err());
+
+        // The other stream keeps flowing over the same channel.
+        for i in 0..100 {
+            busy.send(i).await.unwrap();
+            assert_eq!(busy2.recv().await, Some(i));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let (mux1, mut mux2) = channel::<u32, u32>(4, 2);
+
+        let mut a = mux1.open_stream().await.unwrap();
+        let mut a2 = mux2.accept_stream().await.unwrap();
+
+        a.send(1).await.unwrap();
+        a.close_send();
+        assert_eq!(a2.recv().await, Some(1));
+        assert_eq!(a2.recv().await, None);
+
+        // The other direction is still open.
+        a2.send(2).await.unwrap();
+        assert_eq!(a.recv().await, Some(2));
+
+        drop(a);
+        for i in 0.. {
+            if a2.send(i).await.is_err() {
+                break;
+            }
+        }
+
+        drop(a2);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(mux1.stream_count(), 0);
+        assert_eq!(mux2.stream_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unaccepted_stream() {
+        let (mux1, mux2) = channel::<u32, u32>(4, 2);
+
+        let mut a = mux1.open_stream().await.unwrap();
+        drop(mux2);
+
+        // Streams queued for accepting are dropped with the multiplexer.
+        assert_eq!(a.recv().await, None);
+        while a.send(1).await.is_ok() {}
+    }
+
+    #[tokio::test]
+    async fn test_tasks_end_once_everything_is_dropped() {
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let (mux1, mut mux2) = channel::<u32, u32>(4, 2);
+
+        let a = mux1.open_stream().await.unwrap();
+        let mut a2 = mux2.accept_stream().await.unwrap();
+        a.send(1).await.unwrap();
+        assert_eq!(a2.recv().await, Some(1));
+        // Left unread, and with the window used up.
+        a.send(2).await.unwrap();
+        a.send(3).await.unwrap();
+
+        drop((a, a2, mux1, mux2));
+        for _ in 0..100 {
+            if metrics.num_alive_tasks() == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} tasks still alive", metrics.num_alive_tasks());
+    }
+
+    #[tokio::test]
+    async fn test_underlying_channel_closed() {
+        let (chan1, chan2) = bichannel(4);
+        let mut mux = Multiplexer::<u32, u32>::new(chan1, 2);
+
+        let mut a = mux.open_stream().await.unwrap();
+        drop(chan2);
+
+        assert_eq!(a.recv().await, None);
+        while a.send(1).await.is_ok() {}
+        assert!(mux.accept_stream().await.is_none());
+        assert_eq!(mux.open_stream().await.unwrap_err(), MuxError::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_window_overrun() {
+        let (chan1, raw) = bichannel::<Segment<u32>, Segment<u32>>(64);
+        let mut mux = Multiplexer::<u32, u32>::new(chan1, 2);
+
+        raw.send(Segment::Open { stream: 0 }).await.unwrap();
+        let mut a = mux.accept_stream().await.unwrap();
+
+        // Ignores the credit it was granted.
+        for value in 0..10 {
+            raw.send(Segment::Data { stream: 0, value }).await.unwrap();
+        }
+
+        let mut received = 0;
+        while a.recv().await.is_some() {
+            received += 1;
+        }
+        assert!(received < 10);
+
+        let mut raw = raw;
+        while let Some(segment) = raw.recv().await {
+            if let Segment::Stop { stream } = segment {
+                assert_eq!(stream, 1);
+                return;
+            }
+        }
+        panic!("the stream was not stopped");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_open() {
+        let (chan1, mut raw) = bichannel::<Segment<u32>, Segment<u32>>(16);
+        let mut mux = Multiplexer::<u32, u32>::new(chan1, 2);
+
+        raw.send(Segment::Open { stream: 0 }).await.unwrap();
+        raw.send(Segment::Open { stream: 0 }).await.unwrap();
+        // An ID the peer would use for its own streams.
+        raw.send(Segment::Open { stream: 1 }).await.unwrap();
+
+        let mut a = mux.accept_stream().await.unwrap();
+        assert_eq!(raw.recv().await, Some(Segment::Credit { stream: 1, n: 2 }));
+        let value = 7;
+        raw.send(Segment::Data { stream: 0, value }).await.unwrap();
+        assert_eq!(a.recv().await, Some(value));
+
+        let result = tokio::time::timeout(Duration::from_millis(20), mux.accept_stream()).await;
+        assert!(result.is_err());
+        assert_eq!(mux.stream_count(), 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_remote() {
+        use crate::remote::{bridge, Json};
+
+        let (a, b) = tokio::io::duplex(1024);
+        let (a, _) = bridge::<Segment<String>, Segment<u32>, _, _>(a, Json::new(), 4);
+        let (b, _) = bridge::<Segment<u32>, Segment<String>, _, _>(b, Json::new(), 4);
+        let client = Multiplexer::new(a, 16);
+        let mut server = Multiplexer::new(b, 16);
+
+        tokio::spawn(async move {
+            while let Some(mut stream) = server.accept_stream().await {
+                tokio::spawn(async move {
+                    while let Some(msg) = stream.recv().await {
+                        stream.send(msg.len() as u32).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let mut streams = Vec::new();
+        for _ in 0..5 {
+            streams.push(client.open_stream().await.unwrap());
+        }
+        for (i, stream) in streams.iter().enumerate() {
+            for _ in 0..10 {
+                stream.send("x".repeat(i)).await.unwrap();
+            }
+        }
+        for (i, stream) in streams.iter_mut().enumerate() {
+            for _ in 0..10 {
+                assert_eq!(stream.recv().await, Some(i as u32));
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::sync::atomic::Ordering;
+
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::{Channel, ChannelSender, DeadLetterReason};
+
+/// What happens to a message sent on a full direction, configured with [`Direction::overflow`].
+///
+/// The policy applies to [`Channel::send`], [`Channel::try_send`] and the timed and blocking
+/// variants of `send`. APIs that reserve capacity first, such as [`Channel::reserve`],
+/// [`Channel::send_all`] and the `Sink` implementation, always wait for room.
+///
+/// [`Direction::overflow`]: crate::Direction::overflow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// `send` waits for room and `try_send` fails with [`TrySendError::Full`].
+    #[default]
+    Block,
+    /// `send` fails immediately instead of waiting, handing the message back. `try_send` fails
+    /// with [`TrySendError::Full`], which tells a rejected message apart from a closed channel.
+    Reject,
+    /// The message being sent is discarded, and the send succeeds.
+    ///
+    /// Discarded messages are handed to the dead-letter sink of the direction, if one was set
+    /// with [`Channel::set_dead_letter_sink`].
+    DropNewest,
+    /// The oldest queued message is discarded to make room, and the send succeeds.
+    ///
+    /// If every slot is reserved by a permit rather than holding a message, there is nothing to
+    /// evict, and the message being sent is discarded instead as with [`Overflow::DropNewest`].
+    ///
+    /// Discarded messages are handed to the dead-letter sink of the direction, if one was set
+    /// with [`Channel::set_dead_letter_sink`].
+    EvictOldest,
+}
+
+impl<S> ChannelSender<S> {
+    /// Sends a message without waiting, applying the overflow policy of the direction if it
+    /// is full.
+    pub(crate) fn send_now(&self, s: S) -> Result<(), TrySendError<S>> {
+        let mut retried = false;
+
+        loop {
+            let permit = match self.try_reserve() {
+                Ok(permit) => permit,
+                Err(TrySendError::Closed(())) => {
+                    return self.undeliverable(s).map_err(TrySendError::Closed);
+                }
+                Err(TrySendError::Full(())) => match self.state.overflow {
+                    Overflow::Block | Overflow::Reject => return Err(TrySendError::Full(s)),
+                    Overflow::EvictOldest if self.evict_oldest() => continue,
+                    // The receiver may have emptied the queue meanwhile, so try once more before
+                    // concluding that every slot is reserved by a permit.
+                    Overflow::EvictOldest if !retried => {
+                        retried = true;
+                        continue;
+                    }
+                    Overflow::DropNewest | Overflow::EvictOldest => {
+                        self.state.dropped.fetch_add(1, Ordering::Relaxed);
+
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(
+                            channel = self.label(),
+                            id = self.id,
+                            "dropped message sent on full direction"
+                        );
+
+                        let _ = self.state.dead_letter(s, DeadLetterReason::Dropped);
+                        return Ok(());
+                    }
+                },
+            };
+
+            permit.send(s);
+            return Ok(());
+        }
+    }
+
+    /// Discards the oldest queued message of the direction, returning whether there was one.
+    ///
+    /// Another sender may take the freed slot first, so the caller retries its send either way.
+    fn evict_oldest(&self) -> bool {
+        let Some(queue) = self.state.queue.upgrade() else {
+            return false;
+        };
+
+        let evicted = queue
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .try_recv();
+        let Ok(envelope) = evicted else {
+            return false;
+        };
+        self.state.evicted.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            channel = self.label(),
+            id = self.id,
+            "evicted oldest message of full direction"
+        );
+
+        let _ = self
+            .state
+            .dead_letter(envelope.value, DeadLetterReason::Evicted);
+        true
+    }
+
+    /// Returns the policy applied when sending on a full direction.
+    pub fn overflow(&self) -> Overflow {
+        self.state.overflow
+    }
+
+    /// Returns how many messages sent on this direction were discarded by its [`Overflow`]
+    /// policy.
+    pub fn discarded(&self) -> u64 {
+        self.state.discarded()
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Returns the policy applied when sending to the peer on a full direction.
+    pub fn overflow(&self) -> Overflow {
+        self.sender.overflow()
+    }
+
+    /// Returns how many messages sent to the peer were discarded by the [`Overflow`] policy of
+    /// the direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::{ChannelBuilder, Direction, Overflow};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (telemetry, mut monitor) = ChannelBuilder::new(10)
+    ///     .left_to_right(Direction::bounded(2).overflow(Overflow::EvictOldest))
+    ///     .build::<u32, ()>();
+    ///
+    /// for i in 0..5 {
+    ///     telemetry.send(i).await.unwrap();
+    /// }
+    ///
+    /// assert_eq!(telemetry.discarded(), 3);
+    /// assert_eq!(monitor.recv().await, Some(3));
+    /// assert_eq!(monitor.recv().await, Some(4));
+    /// # }
+    /// ```
+    pub fn discarded(&self) -> u64 {
+        self.sender.discarded()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::error::TrySendError;
+
+    use crate::{ChannelBuilder, Direction, Overflow};
+
+    fn with_policy(capacity: usize, overflow: Overflow) -> ChannelBuilder {
+        ChannelBuilder::new(1).left_to_right(Direction::bounded(capacity).overflow(overflow))
+    }
+
+    #[tokio::test]
+    async fn test_block() {
+        let (chan1, mut chan2) = with_policy(1, Overflow::Block).build::<u32, u32>();
+
+        chan1.send(1).await.unwrap();
+        assert!(matches!(chan1.try_send(2), Err(TrySendError::Full(2))));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), chan1.send(2))
+                .await
+                .is_err()
+        );
+
+        assert_eq!(chan2.recv().await, Some(1));
+        assert_eq!(chan1.discarded(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reject() {
+        let (chan1, mut chan2) = with_policy(1, Overflow::Reject).build::<u32, u32>();
+
+        chan1.send(1).await.unwrap();
+        assert_eq!(chan1.send(2).await.unwrap_err().0, 2);
+        assert!(matches!(chan1.try_send(3), Err(TrySendError::Full(3))));
+
+        assert_eq!(chan2.recv().await, Some(1));
+        assert_eq!(chan1.discarded(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest() {
+        let (chan1, mut chan2) = with_policy(2, Overflow::DropNewest).build::<u32, u32>();
+
+        for i in 0..5 {
+            chan1.send(i).await.unwrap();
+        }
+        chan1.try_send(5).unwrap();
+
+        assert_eq!(chan1.discarded(), 4);
+        assert_eq!(chan2.drain().collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_evict_oldest() {
+        let (chan1, mut chan2) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(3).overflow(Overflow::EvictOldest))
+            .metrics()
+            .build::<u32, u32>();
+
+        for i in 0..10 {
+            chan1.send(i).await.unwrap();
+        }
+        chan1.try_send(10).unwrap();
+
+        assert_eq!(chan1.discarded(), 8);
+        assert_eq!(chan2.drain().collect::<Vec<_>>(), vec![8, 9, 10]);
+
+        let stats = chan2.stats().incoming.unwrap();
+        assert_eq!(stats.sent, 11);
+        assert_eq!(stats.discarded, 8);
+        assert_eq!(stats.queue_depth, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_evict_oldest_concurrent() {
+        let (chan1, chan2) = with_policy(4, Overflow::EvictOldest).build::<u32, u32>();
+        let (tx, _rx) = chan1.split();
+        let (_tx, mut rx) = chan2.split();
+
+        let writers = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    for i in 0..1000 {
+                        tx.send(i).await.unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut received = 0;
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert_eq!(tx.discarded(), 4000 - 4);
+        drop(tx);
+        while rx.recv().await.is_some() {
+            received += 1;
+        }
+
+        assert_eq!(received, 4);
+        assert_eq!(rx.queue_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_evict_oldest_with_permits() {
+        let (chan1, mut chan2) = with_policy(1, Overflow::EvictOldest).build::<u32, u32>();
+
+        // The only slot is reserved, so there is no message to evict.
+        let permit = chan1.reserve().await.unwrap();
+        chan1.try_send(5).unwrap();
+        assert_eq!(chan1.discarded(), 1);
+
+        permit.send(6);
+        assert_eq!(chan2.recv().await, Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let (chan1, chan2) = with_policy(1, Overflow::EvictOldest).build::<u32, u32>();
+        chan1.send(1).await.unwrap();
+        drop(chan2);
+
+        assert!(matches!(chan1.try_send(2), Err(TrySendError::Closed(2))));
+        assert_eq!(chan1.send(3).await.unwrap_err().0, 3);
+    }
+}
@@ -0,0 +1,380 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+
+use crate::metrics::Envelope;
+use crate::split::DirectionState;
+use crate::{Channel, ChannelSender};
+
+/// A reserved slot in a direction of a [`Channel`], created by [`Channel::reserve`].
+///
+/// Sending through a permit never waits and never fails. Dropping a permit without sending
+/// gives the slot back.
+#[derive(Debug)]
+pub struct Permit<'a, S> {
+    inner: mpsc::Permit<'a, Envelope<S>>,
+    sender: &'a ChannelSender<S>,
+}
+
+impl<S> Permit<'_, S> {
+    /// Sends a message using the reserved slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    pub fn send(self, s: S) {
+        self.inner.send(self.sender.envelope(s));
+        self.sender.sent();
+    }
+}
+
+/// An iterator over slots reserved by [`Channel::reserve_many`].
+#[derive(Debug)]
+pub struct PermitIterator<'a, S> {
+    inner: mpsc::PermitIterator<'a, Envelope<S>>,
+    sender: &'a ChannelSender<S>,
+}
+
+impl<'a, S> Iterator for PermitIterator<'a, S> {
+    type Item = Permit<'a, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sender = self.sender;
+        self.inner.next().map(|inner| Permit { inner, sender })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S> ExactSizeIterator for PermitIterator<'_, S> {}
+
+/// A reserved slot that owns its sending half, created by [`ChannelSender::reserve_owned`] or
+/// [`Channel::reserve_owned`].
+///
+/// Unlike [`Permit`], an owned permit is not tied to the lifetime of a channel and can be moved
+/// into another task.
+#[derive(Debug)]
+pub struct OwnedPermit<S> {
+    id: u64,
+    state: Arc<DirectionState<S>>,
+    inner: mpsc::OwnedPermit<Envelope<S>>,
+}
+
+impl<S> OwnedPermit<S> {
+    /// Sends a message using the reserved slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `ChannelSender<S>` - Returns the sending half the slot was reserved on.
+    pub fn send(self, s: S) -> ChannelSender<S> {
+        let sender = self.inner.send(Envelope::new(s, &self.state));
+        let sender = ChannelSender::new(self.id, self.state, sender);
+
+        sender.sent();
+        sender
+    }
+
+    /// Gives the reserved slot back without sending anything.
+    ///
+    /// # Returns
+    ///
+    /// * `ChannelSender<S>` - Returns the sending half the slot was reserved on.
+    pub fn release(self) -> ChannelSender<S> {
+        ChannelSender::new(self.id, self.state, self.inner.release())
+    }
+}
+
+impl<S> ChannelSender<S> {
+    /// Waits for a slot in the direction and reserves it.
+    ///
+    /// This is cancel-safe: if the returned future is dropped, nothing has been sent and no
+    /// message is lost.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Permit<'_, S>, SendError<()>>` - Returns the permit, or an error if the channel is closed.
+    pub async fn reserve(&self) -> Result<Permit<'_, S>, SendError<()>> {
+        let since = self.start_blocking(1);
+        let inner = self.sender.reserve().await;
+        self.stop_blocking(since);
+
+        Ok(Permit {
+            inner: inner?,
+            sender: self,
+        })
+    }
+
+    /// Reserves a slot in the direction without waiting.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Permit<'_, S>, TrySendError<()>>` - Returns the permit, or an error if the channel is full or closed.
+    pub fn try_reserve(&self) -> Result<Permit<'_, S>, TrySendError<()>> {
+        let inner = self.sender.try_reserve()?;
+        Ok(Permit {
+            inner,
+            sender: self,
+        })
+    }
+
+    /// Waits for `n` slots in the direction and reserves them all at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of slots to reserve.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PermitIterator<'_, S>, SendError<()>>` - Returns the permits, or an error if the channel is
+    ///   closed or `n` exceeds the capacity of the direction.
+    pub async fn reserve_many(&self, n: usize) -> Result<PermitIterator<'_, S>, SendError<()>> {
+        let since = self.start_blocking(n);
+        let inner = self.sender.reserve_many(n).await;
+        self.stop_blocking(since);
+
+        Ok(PermitIterator {
+            inner: inner?,
+            sender: self,
+        })
+    }
+
+    /// Reserves `n` slots in the direction without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of slots to reserve.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PermitIterator<'_, S>, TrySendError<()>>` - Returns the permits, or an error if there is not
+    ///   enough capacity or the channel is closed.
+    pub fn try_reserve_many(&self, n: usize) -> Result<PermitIterator<'_, S>, TrySendError<()>> {
+        let inner = self.sender.try_reserve_many(n)?;
+        Ok(PermitIterator {
+            inner,
+            sender: self,
+        })
+    }
+
+    /// Waits for a slot in the direction and reserves it, moving this half into the permit.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<OwnedPermit<S>, SendError<()>>` - Returns the permit, or an error if the channel is closed.
+    pub async fn reserve_owned(self) -> Result<OwnedPermit<S>, SendError<()>> {
+        let since = self.start_blocking(1);
+        let inner = self.sender.clone().reserve_owned().await;
+        self.stop_blocking(since);
+
+        Ok(OwnedPermit {
+            id: self.id,
+            state: self.state,
+            inner: inner?,
+        })
+    }
+
+    /// Reserves a slot in the direction without waiting, moving this half into the permit.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<OwnedPermit<S>, TrySendError<Self>>` - Returns the permit, or this half back if the channel
+    ///   is full or closed.
+    pub fn try_reserve_owned(self) -> Result<OwnedPermit<S>, TrySendError<Self>> {
+        let (id, state) = (self.id, self.state);
+
+        match self.sender.try_reserve_owned() {
+            Ok(inner) => Ok(OwnedPermit { id, state, inner }),
+            Err(TrySendError::Full(sender)) => {
+                Err(TrySendError::Full(ChannelSender::new(id, state, sender)))
+            }
+            Err(TrySendError::Closed(sender)) => {
+                Err(TrySendError::Closed(ChannelSender::new(id, state, sender)))
+            }
+        }
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Waits for a slot to send to the peer and reserves it.
+    ///
+    /// Unlike [`Channel::send`], this is cancel-safe: if the returned future is dropped, for
+    /// example by losing a `tokio::select!` race, no message is lost because none has been built yet.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Permit<'_, S>, SendError<()>>` - Returns the permit, or an error if the channel is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = channel::<String, String>(10);
+    ///
+    /// tokio::select! {
+    ///     permit = chan1.reserve() => permit.unwrap().send("Hello".to_string()),
+    ///     _ = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+    /// }
+    ///
+    /// assert_eq!(chan2.recv().await.unwrap(), "Hello");
+    /// # }
+    /// ```
+    pub async fn reserve(&self) -> Result<Permit<'_, S>, SendError<()>> {
+        self.sender.reserve().await
+    }
+
+    /// Reserves a slot to send to the peer without waiting.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Permit<'_, S>, TrySendError<()>>` - Returns the permit, or an error if the channel is full or closed.
+    pub fn try_reserve(&self) -> Result<Permit<'_, S>, TrySendError<()>> {
+        self.sender.try_reserve()
+    }
+
+    /// Waits for `n` slots to send to the peer and reserves them all at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of slots to reserve.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PermitIterator<'_, S>, SendError<()>>` - Returns the permits, or an error if the channel is
+    ///   closed or `n` exceeds [`Channel::max_capacity`].
+    pub async fn reserve_many(&self, n: usize) -> Result<PermitIterator<'_, S>, SendError<()>> {
+        self.sender.reserve_many(n).await
+    }
+
+    /// Reserves `n` slots to send to the peer without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of slots to reserve.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PermitIterator<'_, S>, TrySendError<()>>` - Returns the permits, or an error if there is not
+    ///   enough capacity or the channel is closed.
+    pub fn try_reserve_many(&self, n: usize) -> Result<PermitIterator<'_, S>, TrySendError<()>> {
+        self.sender.try_reserve_many(n)
+    }
+
+    /// Waits for a slot to send to the peer and reserves it with a permit that can be moved
+    /// into another task.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<OwnedPermit<S>, SendError<()>>` - Returns the permit, or an error if the channel is closed.
+    pub async fn reserve_owned(&self) -> Result<OwnedPermit<S>, SendError<()>> {
+        self.sender.clone().reserve_owned().await
+    }
+
+    /// Reserves a slot to send to the peer without waiting, with a permit that can be moved
+    /// into another task.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<OwnedPermit<S>, TrySendError<()>>` - Returns the permit, or an error if the channel is full or closed.
+    pub fn try_reserve_owned(&self) -> Result<OwnedPermit<S>, TrySendError<()>> {
+        self.sender
+            .clone()
+            .try_reserve_owned()
+            .map_err(|e| match e {
+                TrySendError::Full(_) => TrySendError::Full(()),
+                TrySendError::Closed(_) => TrySendError::Closed(()),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::error::TrySendError;
+
+    use crate::channel;
+
+    #[tokio::test]
+    async fn test_reserve_cancelled() {
+        let (chan1, mut chan2) = channel::<u32, u32>(1);
+        chan1.send(1).await.unwrap();
+
+        let mut built = 0;
+        tokio::select! {
+            permit = chan1.reserve() => {
+                built += 1;
+                permit.unwrap().send(2);
+            }
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+        }
+        assert_eq!(built, 0);
+
+        assert_eq!(chan2.recv().await, Some(1));
+        chan1.reserve().await.unwrap().send(3);
+        assert_eq!(chan2.recv().await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_try_reserve() {
+        let (chan1, mut chan2) = channel::<u32, u32>(1);
+
+        let permit = chan1.try_reserve().unwrap();
+        assert_eq!(chan1.capacity(), Some(0));
+        assert!(matches!(chan1.try_reserve(), Err(TrySendError::Full(()))));
+        assert!(matches!(chan1.try_send(1), Err(TrySendError::Full(1))));
+
+        drop(permit);
+        chan1.try_reserve().unwrap().send(2);
+        assert_eq!(chan2.recv().await, Some(2));
+
+        drop(chan2);
+        assert!(matches!(chan1.try_reserve(), Err(TrySendError::Closed(()))));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_many() {
+        let (chan1, mut chan2) = channel::<u32, u32>(4);
+
+        let permits = chan1.reserve_many(3).await.unwrap();
+        assert_eq!(permits.len(), 3);
+        assert!(chan1.try_reserve_many(2).is_err());
+
+        for (i, permit) in permits.enumerate() {
+            permit.send(i as u32);
+        }
+        for i in 0..3 {
+            assert_eq!(chan2.recv().await, Some(i));
+        }
+
+        assert!(chan1.reserve_many(5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reserve_owned() {
+        let (chan1, mut chan2) = channel::<u32, u32>(1);
+
+        let permit = chan1.reserve_owned().await.unwrap();
+        assert!(chan1.try_reserve_owned().is_err());
+
+        tokio::spawn(async move {
+            permit.send(1);
+        })
+        .await
+        .unwrap();
+        assert_eq!(chan2.recv().await, Some(1));
+
+        let (tx, _rx) = chan1.split();
+        let tx = tx.try_reserve_owned().unwrap().release();
+        tx.reserve_owned().await.unwrap().send(2);
+        assert_eq!(chan2.recv().await, Some(2));
+    }
+}
@@ -0,0 +1,344 @@
+use std::future::poll_fn;
+use std::task::{Context, Poll};
+
+use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+use tokio::sync::mpsc::{channel as create_channel, Receiver, Sender};
+
+/// How many times in a row a waiting lane may be passed over by higher lanes by default.
+pub const DEFAULT_STARVATION_LIMIT: u32 = 16;
+
+/// A bidirectional channel where each direction is split into a fixed number of lanes, and
+/// messages on higher lanes are received first.
+///
+/// Lanes are numbered from `0`, the lowest priority, to `lanes - 1`, the highest. Each lane
+/// has its own capacity, so a flood of low priority messages never keeps a high priority
+/// message from being sent. Messages within a lane are received in order.
+///
+/// To avoid starvation, a lane that has messages waiting but was passed over
+/// [`PriorityChannel::starvation_limit`] times in a row is served before the higher lanes.
+///
+/// # Examples
+///
+/// ```
+/// # use tokio_bichannel::priority_channel;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (controller, mut worker) = priority_channel::<&str, ()>(2, 100);
+///
+/// controller.send("data 1").await.unwrap();
+/// controller.send("data 2").await.unwrap();
+/// controller.send_with_priority("cancel", 1).await.unwrap();
+///
+/// assert_eq!(worker.recv().await.unwrap(), "cancel");
+/// assert_eq!(worker.recv().await.unwrap(), "data 1");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PriorityChannel<S, R> {
+    senders: Vec<Sender<S>>,
+    receivers: Vec<Receiver<R>>,
+    // How many times in a row each lane had messages waiting but a higher lane was served.
+    skipped: Vec<u32>,
+    starvation_limit: u32,
+}
+
+impl<S, R> PriorityChannel<S, R> {
+    /// Sets how many times in a row a lane with messages waiting may be passed over by higher
+    /// lanes before it is served first.
+    ///
+    /// This only affects the order in which this end receives messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The number of messages from higher lanes received before a waiting lane
+    ///   gets its turn, [`DEFAULT_STARVATION_LIMIT`] by default. `u32::MAX` disables starvation
+    ///   avoidance.
+    pub fn starvation_limit(mut self, limit: u32) -> Self {
+        self.starvation_limit = limit;
+        self
+    }
+
+    /// Returns the number of lanes in each direction.
+    pub fn lanes(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Sends a message on the lowest priority lane.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    pub async fn send(&self, s: S) -> Result<(), SendError<S>> {
+        self.send_with_priority(s, 0).await
+    }
+
+    /// Sends a message on the lane `level`, waiting if that lane is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `level` - The lane to send on, from `0`, the lowest priority, to `lanes - 1`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if it wasn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not lower than the number of lanes.
+    pub async fn send_with_priority(&self, s: S, level: usize) -> Result<(), SendError<S>> {
+        self.lane(level).send(s).await
+    }
+
+    /// Attempts to send a message on the lane `level` without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `level` - The lane to send on, from `0`, the lowest priority, to `lanes - 1`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), TrySendError<S>>` - Returns `Ok(())` if the message was sent, or an error if the lane is full or the channel is closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not lower than the number of lanes.
+    pub fn try_send_with_priority(&self, s: S, level: usize) -> Result<(), TrySendError<S>> {
+        self.lane(level).try_send(s)
+    }
+
+    /// Receives the next message from the peer, taking it from the highest lane that has one
+    /// unless a lower lane is starving.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<R>` - Returns `Some(message)` if a message was received, or `None` if the channel is closed.
+    pub async fn recv(&mut self) -> Option<R> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Attempts to receive the next message from the peer without blocking.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, TryRecvError>` - Returns `Ok(message)` if a message was received, or an error if the channel is empty or closed.
+    pub fn try_recv(&mut self) -> Result<R, TryRecvError> {
+        if let Some(lane) = self.starving() {
+            if let Ok(r) = self.receivers[lane].try_recv() {
+                self.served(lane);
+                return Ok(r);
+            }
+        }
+
+        let mut result = Err(TryRecvError::Disconnected);
+        for lane in (0..self.receivers.len()).rev() {
+            match self.receivers[lane].try_recv() {
+                Ok(r) => {
+                    self.served(lane);
+                    return Ok(r);
+                }
+                Err(TryRecvError::Empty) => result = Err(TryRecvError::Empty),
+                Err(TryRecvError::Disconnected) => {}
+            }
+        }
+
+        result
+    }
+
+    /// Returns the number of messages waiting to be received on the lane `level`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is not lower than the number of lanes.
+    pub fn queue_len(&self, level: usize) -> usize {
+        self.receivers[level].len()
+    }
+
+    fn lane(&self, level: usize) -> &Sender<S> {
+        assert!(
+            level < self.senders.len(),
+            "priority {} is out of range for {} lanes",
+            level,
+            self.senders.len()
+        );
+        &self.senders[level]
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        if let Some(lane) = self.starving() {
+            if let Ok(r) = self.receivers[lane].try_recv() {
+                self.served(lane);
+                return Poll::Ready(Some(r));
+            }
+        }
+
+        let mut closed = 0;
+        for lane in (0..self.receivers.len()).rev() {
+            match self.receivers[lane].poll_recv(cx) {
+                Poll::Ready(Some(r)) => {
+                    self.served(lane);
+                    return Poll::Ready(Some(r));
+                }
+                Poll::Ready(None) => closed += 1,
+                Poll::Pending => {}
+            }
+        }
+
+        if closed == self.receivers.len() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Returns the highest lane that was passed over too many times.
+    fn starving(&self) -> Option<usize> {
+        self.skipped
+            .iter()
+            .rposition(|skipped| *skipped >= self.starvation_limit)
+    }
+
+    /// Updates the starvation counters after a message was received from `lane`.
+    fn served(&mut self, lane: usize) {
+        self.skipped[lane] = 0;
+
+        for lower in 0..lane {
+            if !self.receivers[lower].is_empty() {
+                self.skipped[lower] = self.skipped[lower].saturating_add(1);
+            }
+        }
+    }
+}
+
+/// Creates a bidirectional channel whose directions are split into priority lanes.
+///
+/// # Arguments
+///
+/// * `lanes` - The number of lanes in each direction.
+/// * `lane_capacity` - The size of the buffer of each lane.
+///
+/// # Returns
+///
+/// * `(PriorityChannel<T, U>, PriorityChannel<U, T>)` - Returns a tuple of two `PriorityChannel` instances.
+///
+/// # Panics
+///
+/// Panics if `lanes` or `lane_capacity` is zero.
+pub fn priority_channel<T, U>(
+    lanes: usize,
+    lane_capacity: usize,
+) -> (PriorityChannel<T, U>, PriorityChannel<U, T>) {
+    assert!(lanes > 0, "a priority channel needs at least one lane");
+
+    let (ls, lr): (Vec<_>, Vec<_>) = (0..lanes).map(|_| create_channel(lane_capacity)).unzip();
+    let (rs, rr): (Vec<_>, Vec<_>) = (0..lanes).map(|_| create_channel(lane_capacity)).unzip();
+
+    (
+        PriorityChannel {
+            senders: ls,
+            receivers: rr,
+            skipped: vec![0; lanes],
+            starvation_limit: DEFAULT_STARVATION_LIMIT,
+        },
+        PriorityChannel {
+            senders: rs,
+            receivers: lr,
+            skipped: vec![0; lanes],
+            starvation_limit: DEFAULT_STARVATION_LIMIT,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_priority_order() {
+        let (chan1, mut chan2) = priority_channel::<u32, u32>(3, 10);
+
+        for i in 0..3 {
+            chan1.send_with_priority(i, 0).await.unwrap();
+            chan1.send_with_priority(10 + i, 1).await.unwrap();
+            chan1.send_with_priority(20 + i, 2).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        while let Ok(r) = chan2.try_recv() {
+            received.push(r);
+        }
+        assert_eq!(received, vec![20, 21, 22, 10, 11, 12, 0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_recv_waits_on_every_lane() {
+        let (chan1, mut chan2) = priority_channel::<u32, u32>(2, 1);
+
+        let receiver = tokio::spawn(async move { chan2.recv().await });
+        tokio::task::yield_now().await;
+        chan1.send(7).await.unwrap();
+
+        assert_eq!(receiver.await.unwrap(), Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_lane_capacity() {
+        let (chan1, _chan2) = priority_channel::<u32, u32>(2, 2);
+
+        chan1.try_send_with_priority(0, 0).unwrap();
+        chan1.try_send_with_priority(1, 0).unwrap();
+        assert!(matches!(
+            chan1.try_send_with_priority(2, 0),
+            Err(TrySendError::Full(2))
+        ));
+
+        // A full low lane does not hold back the high lane.
+        chan1.try_send_with_priority(3, 1).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_starvation_avoidance() {
+        let (chan1, chan2) = priority_channel::<u32, u32>(2, 100);
+        let mut chan2 = chan2.starvation_limit(3);
+
+        chan1.send_with_priority(0, 0).await.unwrap();
+        chan1.send_with_priority(1, 0).await.unwrap();
+        for i in 0..10 {
+            chan1.send_with_priority(100 + i, 1).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        for _ in 0..12 {
+            received.push(chan2.recv().await.unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![100, 101, 102, 0, 103, 104, 105, 1, 106, 107, 108, 109]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let (chan1, mut chan2) = priority_channel::<u32, u32>(2, 4);
+
+        chan1.send_with_priority(1, 1).await.unwrap();
+        drop(chan1);
+
+        assert_eq!(chan2.recv().await, Some(1));
+        assert_eq!(chan2.recv().await, None);
+        assert!(matches!(chan2.try_recv(), Err(TryRecvError::Disconnected)));
+        assert!(chan2.send(2).await.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_level_out_of_range() {
+        let (chan1, _chan2) = priority_channel::<u32, u32>(2, 4);
+        let _ = chan1.try_send_with_priority(1, 2);
+    }
+}
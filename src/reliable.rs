@@ -0,0 +1,637 @@
+//! At-least-once delivery over a [`Channel`] that may lose messages, such as one started with
+//! [`remote::Reconnect`].
+//!
+//! [`remote::Reconnect`]: crate::remote::Reconnect
+
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::sync::mpsc::error::TrySendError;
+#[cfg(feature = "remote")]
+use tokio::sync::watch;
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Instant};
+
+#[cfg(feature = "remote")]
+use crate::remote::ConnectionState;
+use crate::{channel, Channel, ChannelReceiver, ChannelSender};
+
+/// A packet of a reliable session as it travels over the underlying channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "remote", derive(serde::Serialize, serde::Deserialize))]
+pub enum Packet<T> {
+    /// A message, numbered from 0 in the order it was sent.
+    Data {
+        /// The sequence number of the message.
+        seq: u64,
+        /// The message.
+        value: T,
+    },
+    /// Marks the end of the messages, numbered after the last one.
+    Fin {
+        /// The sequence number of the end.
+        seq: u64,
+    },
+    /// Acknowledges every message, and the end, with a sequence number lower than `next`.
+    Ack {
+        /// The sequence number of the next message expected.
+        next: u64,
+    },
+}
+
+/// The acknowledgement state shared by the two tasks of a session.
+#[derive(Debug, Default)]
+struct Acks {
+    // Everything before this was acknowledged by the peer.
+    peer_next: u64,
+    // An acknowledgement to send to the peer.
+    pending: Option<u64>,
+    // The peer will send no more messages, but may still need acknowledgements.
+    finished: bool,
+    closed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    acks: Mutex<Acks>,
+    notify: Notify,
+}
+
+impl Shared {
+    fn update(&self, f: impl FnOnce(&mut Acks)) {
+        f(&mut self.acks.lock().unwrap_or_else(PoisonError::into_inner));
+        self.notify.notify_one();
+    }
+}
+
+/// A session that delivers every message at least once, in order and without duplicates,
+/// over a channel that may lose messages.
+///
+/// Each message gets a sequence number and is kept until the peer acknowledges it. The peer
+/// acknowledges every message it receives in order, and drops duplicates and messages that
+/// arrive after a gap. Whenever nothing is acknowledged for the retransmit timeout, every
+/// unacknowledged message is sent again, which recovers the messages in flight when the
+/// underlying connection was lost. When the session knows about the connection, through
+/// [`Reliable::reconnects`], they are sent again as soon as it is back instead.
+///
+/// Acknowledgements keep flowing while the application is slow to receive. Once its buffer
+/// is full, one more message is held for it and the ones after it are left unacknowledged,
+/// so the peer sends them again after the retransmit timeout.
+///
+/// Both ends of the underlying channel must run a session.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::channel;
+/// use tokio_bichannel::reliable::{Packet, Reliable};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (a, b) = channel::<Packet<String>, Packet<String>>(10);
+///
+/// let mut a = Reliable::new(a).window(32).start();
+/// let mut b = Reliable::new(b).window(32).start();
+///
+/// a.send("Hello".to_string()).await.unwrap();
+/// assert_eq!(b.recv().await.unwrap(), "Hello");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Reliable<S, R> {
+    transport: Channel<Packet<S>, Packet<R>>,
+    window: usize,
+    retransmit_after: Duration,
+    buffer_size: usize,
+    #[cfg(feature = "remote")]
+    reconnects: Option<watch::Receiver<ConnectionState>>,
+}
+
+impl<S, R> Reliable<S, R>
+where
+    S: Clone + Send + 'static,
+    R: Send + 'static,
+{
+    /// Creates a session configuration over `transport`.
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - The channel to run the session over.
+    ///
+    /// # Returns
+    ///
+    /// * `Reliable<S, R>` - Returns the configuration.
+    pub fn new(transport: Channel<Packet<S>, Packet<R>>) -> Self {
+        Self {
+            transport,
+            window: 64,
+            retransmit_after: Duration::from_secs(1),
+            buffer_size: 64,
+            #[cfg(feature = "remote")]
+            reconnects: None,
+        }
+    }
+
+    /// Sets how many messages may be sent but not yet acknowledged. Sending waits while the
+    /// window is full. Defaults to 64.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The largest number of unacknowledged messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn window(mut self, window: usize) -> Self {
+        assert!(window > 0, "the window must be at least one message");
+        self.window = window;
+        self
+    }
+
+    /// Sets how long to wait for an acknowledgement before sending the unacknowledged messages
+    /// again. Defaults to one second.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The retransmit timeout.
+    pub fn retransmit_after(mut self, timeout: Duration) -> Self {
+        self.retransmit_after = timeout;
+        self
+    }
+
+    /// Sends the unacknowledged messages again every time the underlying connection comes
+    /// back, rather than waiting for the retransmit timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The connection state of the transport, as returned by
+    ///   [`Reconnect::start`].
+    ///
+    /// [`Reconnect::start`]: crate::remote::Reconnect::start
+    #[cfg(feature = "remote")]
+    pub fn reconnects(mut self, state: watch::Receiver<ConnectionState>) -> Self {
+        self.reconnects = Some(state);
+        self
+    }
+
+    /// Sets the size of the buffer for each direction of the returned channel. Defaults to 64.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer_size` - The size of the buffer for each direction.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Starts the session and returns the channel the application uses.
+    ///
+    /// This spawns tasks that run the session, so it must be called from within a Tokio
+    /// runtime. Once the application stops sending, the session waits for every message to be
+    /// acknowledged and then closes the sending half of the underlying channel.
+    ///
+    /// # Returns
+    ///
+    /// * `Channel<S, R>` - Returns the channel.
+    pub fn start(self) -> Channel<S, R> {
+        let (app, local) = channel::<S, R>(self.buffer_size);
+        let (to_app, from_app) = local.split();
+        let (transport_tx, transport_rx) = self.transport.split();
+        let shared = Arc::new(Shared::default());
+
+        tokio::spawn(write_packets(
+            from_app,
+            transport_tx,
+            shared.clone(),
+            self.window,
+            self.retransmit_after,
+            #[cfg(feature = "remote")]
+            self.reconnects,
+        ));
+        tokio::spawn(read_packets(transport_rx, to_app, shared));
+
+        app
+    }
+}
+
+enum Event<S> {
+    App(Option<S>),
+    Woken,
+    #[cfg(feature = "remote")]
+    Reconnected,
+}
+
+/// Waits until the connection behind `state` is up again, or forever without one.
+#[cfg(feature = "remote")]
+async fn reconnected(state: Option<&mut watch::Receiver<ConnectionState>>) {
+    if let Some(state) = state {
+        while state.changed().await.is_ok() {
+            if matches!(*state.borrow_and_update(), ConnectionState::Connected) {
+                return;
+            }
+        }
+    }
+
+    std::future::pending().await
+}
+
+async fn write_packets<S: Clone>(
+    mut from_app: ChannelReceiver<S>,
+    transport: ChannelSender<Packet<S>>,
+    shared: Arc<Shared>,
+    window: usize,
+    retransmit_after: Duration,
+    #[cfg(feature = "remote")] mut reconnects: Option<watch::Receiver<ConnectionState>>,
+) {
+    let mut unacked = VecDeque::<(u64, Packet<S>)>::new();
+    let mut next_seq = 0;
+    let mut deadline = None;
+    // Set once the connection came back, losing whatever was in flight.
+    let mut resend = false;
+    let mut sending = true;
+
+    loop {
+        let (peer_next, pending, peer_done, receiving) = {
+            let mut acks = shared.acks.lock().unwrap_or_else(PoisonError::into_inner);
+            (
+                acks.peer_next,
+                acks.pending.take(),
+                acks.finished || acks.closed,
+                !acks.closed,
+            )
+        };
+
+        let before = unacked.len();
+        while unacked.front().is_some_and(|(seq, _)| *seq < peer_next) {
+            unacked.pop_front();
+        }
+        if unacked.len() < before {
+            deadline = (!unacked.is_empty()).then(|| Instant::now() + retransmit_after);
+        }
+
+        if let Some(next) = pending {
+            if transport.send(Packet::Ack { next }).await.is_err() {
+                break;
+            }
+        }
+
+        // The transport is only closed once neither end needs anything more from it.
+        if !sending && ((unacked.is_empty() && peer_done) || !receiving) {
+            break;
+        }
+
+        if resend || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                count = unacked.len(),
+                "retransmitting unacknowledged messages"
+            );
+
+            let packets = unacked
+                .iter()
+                .map(|(_, packet)| packet.clone())
+                .collect::<Vec<_>>();
+            for packet in packets {
+                if transport.send(packet).await.is_err() {
+                    from_app.close();
+                    return;
+                }
+            }
+            deadline = (!unacked.is_empty()).then(|| Instant::now() + retransmit_after);
+            resend = false;
+            continue;
+        }
+
+        let event = {
+            let accepting = sending && unacked.len() < window;
+            let mut recv = pin!(from_app.recv());
+            let mut woken = pin!(shared.notify.notified());
+            let mut timer = pin!(deadline.map(sleep_until));
+            #[cfg(feature = "remote")]
+            let mut reconnected = pin!(reconnected(reconnects.as_mut()));
+
+            poll_fn(|cx| {
+                #[cfg(feature = "remote")]
+                if reconnected.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Event::Reconnected);
+                }
+                if accepting {
+                    if let Poll::Ready(s) = recv.as_mut().poll(cx) {
+                        return Poll::Ready(Event::App(s));
+                    }
+                }
+                if woken.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Event::Woken);
+                }
+                match timer.as_mut().as_pin_mut() {
+                    Some(timer) => timer.poll(cx).map(|_| Event::Woken),
+                    None => Poll::Pending,
+                }
+            })
+            .await
+        };
+
+        let seq = next_seq;
+        let packet = match event {
+            Event::App(Some(value)) => Packet::Data { seq, value },
+            Event::App(None) => {
+                sending = false;
+                Packet::Fin { seq }
+            }
+            Event::Woken => continue,
+            #[cfg(feature = "remote")]
+            Event::Reconnected => {
+                resend = true;
+                continue;
+            }
+        };
+
+        next_seq += 1;
+        unacked.push_back((seq, packet.clone()));
+        deadline.get_or_insert_with(|| Instant::now() + retransmit_after);
+
+        if transport.send(packet).await.is_err() {
+            break;
+        }
+    }
+
+    from_app.close();
+}
+
+async fn read_packets<R>(
+    mut transport: ChannelReceiver<Packet<R>>,
+    to_app: ChannelSender<R>,
+    shared: Arc<Shared>,
+) {
+    let mut to_app = Some(to_app);
+    let mut expected = 0;
+    // A message the application has no room for yet. Packets keep being read meanwhile, so
+    // that acknowledgements from the peer are not held up by a slow application.
+    let mut held = None;
+
+    loop {
+        let packet = match (&to_app, held.take()) {
+            (Some(app), Some(value)) => {
+                let mut room = pin!(app.reserve());
+                let mut recv = pin!(transport.recv());
+                let next = poll_fn(|cx| {
+                    if let Poll::Ready(permit) = room.as_mut().poll(cx) {
+                        return Poll::Ready(Err(permit));
+                    }
+                    recv.as_mut().poll(cx).map(Ok)
+                })
+                .await;
+
+                match next {
+                    Ok(packet) => {
+                        held = Some(value);
+                        packet
+                    }
+                    Err(permit) => {
+                        if let Ok(permit) = permit {
+                            permit.send(value);
+                        }
+                        continue;
+                    }
+                }
+            }
+            _ => transport.recv().await,
+        };
+        let Some(packet) = packet else {
+            break;
+        };
+
+        match packet {
+            // Duplicates and messages after a gap are dropped, as are messages arriving while
+            // one is held; the sender sends them again until they are acknowledged.
+            Packet::Data { seq, value } => {
+                if seq == expected && held.is_none() {
+                    expected += 1;
+                    // Still acknowledged if the application stopped receiving, so that the
+                    // peer does not retransmit forever.
+                    if let Some(to_app) = &to_app {
+                        if let Err(TrySendError::Full(value)) = to_app.try_send(value) {
+                            held = Some(value);
+                        }
+                    }
+                }
+                shared.update(|acks| acks.pending = Some(expected));
+            }
+            Packet::Fin { seq } => {
+                if seq == expected && held.is_none() {
+                    expected += 1;
+                    to_app = None;
+                }
+                shared.update(|acks| {
+                    acks.pending = Some(expected);
+                    acks.finished |= to_app.is_none();
+                });
+            }
+            Packet::Ack { next } => {
+                shared.update(|acks| acks.peer_next = acks.peer_next.max(next));
+            }
+        }
+    }
+
+    shared.update(|acks| acks.closed = true);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Relays messages between `a` and `b`, dropping every 7th and duplicating every 5th.
+    async fn lossy_relay<T: Clone + Send + 'static>(a: Channel<T, T>, b: Channel<T, T>) {
+        let (a_tx, a_rx) = a.split();
+        let (b_tx, b_rx) = b.split();
+
+        let forward = |mut rx: ChannelReceiver<T>, tx: ChannelSender<T>| async move {
+            let mut n = 0;
+            while let Some(msg) = rx.recv().await {
+                n += 1;
+                if n % 7 == 0 {
+                    continue;
+                }
+                if n % 5 == 0 && tx.send(msg.clone()).await.is_err() {
+                    break;
+                }
+                if tx.send(msg).await.is_err() {
+                    break;
+                }
+            }
+        };
+
+        let a_to_b = tokio::spawn(forward(a_rx, b_tx));
+        forward(b_rx, a_tx).await;
+        let _ = a_to_b.await;
+    }
+
+    #[tokio::test]
+    async fn test_lossy_transport() {
+        let (a, relay_a) = channel::<Packet<u32>, Packet<u32>>(16);
+        let (b, relay_b) = channel::<Packet<u32>, Packet<u32>>(16);
+        tokio::spawn(lossy_relay(relay_a, relay_b));
+
+        let session = |transport| {
+            Reliable::new(transport)
+                .window(8)
+                .retransmit_after(Duration::from_millis(10))
+                .start()
+        };
+        let a = session(a);
+        let mut b = session(b);
+
+        let echo = tokio::spawn(async move {
+            while let Some(n) = b.recv().await {
+                b.send(n * 2).await.unwrap();
+            }
+        });
+
+        let (tx, mut rx) = a.split();
+        let sender = tokio::spawn(async move {
+            for i in 0..200 {
+                tx.send(i).await.unwrap();
+            }
+        });
+
+        for i in 0..200 {
+            assert_eq!(rx.recv().await, Some(i * 2));
+        }
+        sender.await.unwrap();
+        drop(rx);
+        echo.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_closes_after_everything_is_acknowledged() {
+        let (a, b) = channel::<Packet<u32>, Packet<u32>>(4);
+        let a = Reliable::new(a).start();
+        let mut b = Reliable::new(b).start();
+
+        for i in 0..10 {
+            a.send(i).await.unwrap();
+        }
+        drop(a);
+
+        for i in 0..10 {
+            assert_eq!(b.recv().await, Some(i));
+        }
+        assert_eq!(b.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_receiver_does_not_hold_up_acks() {
+        let (a, b) = channel::<Packet<u32>, Packet<u32>>(16);
+        let start = |transport| {
+            Reliable::new(transport)
+                .window(4)
+                .buffer_size(1)
+                .retransmit_after(Duration::from_secs(60))
+                .start()
+        };
+        let mut a = start(a);
+        let b = start(b);
+
+        // Fills the buffer of `b`, which is never read.
+        for i in 0..4 {
+            a.send(i).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Each send past the window needs an acknowledgement read by `b`.
+        let exchange = async {
+            for i in 0..20 {
+                b.send(i).await.unwrap();
+                assert_eq!(a.recv().await, Some(i));
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), exchange)
+            .await
+            .unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test(start_paused = true)]
+    async fn test_killed_connection() {
+        use std::io;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use tokio::io::{duplex, AsyncReadExt, DuplexStream};
+        use tokio::sync::mpsc;
+
+        use crate::remote::{Json, Reconnect};
+
+        // Every client dial creates a connection that is cut after a few windows from the
+        // client, dropping whatever is still in flight, including half of a frame.
+        let (accept_tx, accept_rx) = mpsc::unbounded_channel::<DuplexStream>();
+        let accept_rx = Arc::new(tokio::sync::Mutex::new(accept_rx));
+        let dials = Arc::new(AtomicUsize::new(0));
+
+        let dial_client = {
+            let dials = dials.clone();
+            move || {
+                let (client, client_wire) = duplex(256);
+                let (server, server_wire) = duplex(256);
+                tokio::spawn(async move {
+                    let (client_read, mut client_write) = tokio::io::split(client_wire);
+                    let (mut server_read, mut server_write) = tokio::io::split(server_wire);
+                    let mut upstream = client_read.take(1000);
+                    tokio::select! {
+                        _ = tokio::io::copy(&mut upstream, &mut server_write) => {}
+                        _ = tokio::io::copy(&mut server_read, &mut client_write) => {}
+                    }
+                });
+                dials.fetch_add(1, Ordering::Relaxed);
+                let _ = accept_tx.send(server);
+                async move { io::Result::Ok(client) }
+            }
+        };
+        let dial_server = move || {
+            let accept_rx = accept_rx.clone();
+            async move {
+                let server = accept_rx.lock().await.recv().await;
+                server.ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionAborted))
+            }
+        };
+
+        let backoff = Duration::from_millis(1);
+        let client = Reconnect::new(dial_client, Json::new())
+            .backoff(backoff, backoff)
+            .start::<Packet<u32>, Packet<u32>, _, _>();
+        let server = Reconnect::new(dial_server, Json::new())
+            .backoff(backoff, backoff)
+            .start::<Packet<u32>, Packet<u32>, _, _>();
+
+        // The retransmit timeout is never reached, so only resending on reconnect recovers
+        // the messages lost with each connection.
+        let retransmit_after = Duration::from_secs(3600);
+        let session = |(transport, state)| {
+            Reliable::new(transport)
+                .window(16)
+                .retransmit_after(retransmit_after)
+                .reconnects(state)
+                .start()
+        };
+        let client = session(client);
+        let mut server = session(server);
+        let start = Instant::now();
+
+        let sender = tokio::spawn(async move {
+            for i in 0..300 {
+                client.send(i).await.unwrap();
+            }
+            client
+        });
+
+        for i in 0..300 {
+            assert_eq!(server.recv().await, Some(i));
+        }
+        assert!(start.elapsed() < retransmit_after);
+        assert!(dials.load(Ordering::Relaxed) >= 3);
+        sender.await.unwrap();
+    }
+}
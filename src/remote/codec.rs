@@ -0,0 +1,599 @@
+#[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+use bytes::Buf;
+#[cfg(any(
+    feature = "json",
+    feature = "bincode",
+    feature = "msgpack",
+    feature = "cbor"
+))]
+use bytes::BufMut;
+use bytes::BytesMut;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::RemoteError;
+
+/// Turns messages into frames on a byte stream and back, used by [`bridge`].
+///
+/// A codec is responsible both for the wire format of a single message and for delimiting
+/// messages on the stream.
+///
+/// [`bridge`]: super::bridge
+pub trait Codec {
+    /// Encodes `item` as a single frame appended to `dst`.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The message to encode.
+    /// * `dst` - The buffer the frame is appended to.
+    fn encode<T: Serialize>(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), RemoteError>;
+
+    /// Decodes a single frame from the front of `src`, removing it from the buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - The bytes read from the stream so far.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<T>, RemoteError>` - Returns the message, `None` if `src` does not hold a complete
+    ///   frame yet, or an error if the frame is invalid.
+    fn decode<T: DeserializeOwned>(&mut self, src: &mut BytesMut)
+        -> Result<Option<T>, RemoteError>;
+}
+
+/// The largest frame accepted by default, in bytes.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+/// Length-prefixed framing used by the binary codecs.
+///
+/// Each frame starts with its length as a big-endian unsigned integer of `prefix_len` bytes.
+/// Frames longer than `max_frame_size` are rejected with [`RemoteError::FrameTooLarge`] as soon
+/// as their prefix is read, before any memory is reserved for them.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "bincode")]
+/// # {
+/// use tokio_bichannel::remote::{Bincode, LengthDelimited};
+///
+/// let codec = Bincode::new().framing(LengthDelimited::new().prefix_len(2).max_frame_size(1024));
+/// # }
+/// ```
+#[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+#[derive(Debug, Clone, Copy)]
+pub struct LengthDelimited {
+    prefix_len: usize,
+    max_frame_size: usize,
+}
+
+#[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+impl LengthDelimited {
+    /// Creates a framing with a 4-byte prefix and a maximum frame size of
+    /// [`DEFAULT_MAX_FRAME_SIZE`].
+    pub fn new() -> Self {
+        Self {
+            prefix_len: 4,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// Sets the size of the length prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix_len` - The number of bytes of the prefix, from 1 to 8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix_len` is not between 1 and 8.
+    pub fn prefix_len(mut self, prefix_len: usize) -> Self {
+        assert!(
+            (1..=8).contains(&prefix_len),
+            "length prefix must be between 1 and 8 bytes"
+        );
+        self.prefix_len = prefix_len;
+        self
+    }
+
+    /// Sets the largest frame that may be sent or received.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_frame_size` - The maximum length of a frame, in bytes, not counting its prefix.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    // The largest length that both fits in the prefix and is allowed.
+    fn limit(&self) -> u64 {
+        let max = self.max_frame_size as u64;
+        match self.prefix_len {
+            8 => max,
+            n => max.min((1 << (8 * n)) - 1),
+        }
+    }
+
+    /// Appends a frame whose body is written by `write`.
+    fn encode<F>(&self, dst: &mut BytesMut, write: F) -> Result<(), RemoteError>
+    where
+        F: FnOnce(bytes::buf::Writer<&mut BytesMut>) -> Result<(), RemoteError>,
+    {
+        let start = dst.len();
+        dst.put_uint(0, self.prefix_len);
+        write(dst.writer())?;
+
+        let len = (dst.len() - start - self.prefix_len) as u64;
+        if len > self.limit() {
+            dst.truncate(start);
+            return Err(RemoteError::FrameTooLarge(len));
+        }
+
+        let prefix = &len.to_be_bytes()[8 - self.prefix_len..];
+        dst[start..start + self.prefix_len].copy_from_slice(prefix);
+        Ok(())
+    }
+
+    /// Removes the body of the first frame from `src`, if it is complete.
+    fn decode(&self, src: &mut BytesMut) -> Result<Option<BytesMut>, RemoteError> {
+        if src.len() < self.prefix_len {
+            return Ok(None);
+        }
+
+        let len = (&src[..self.prefix_len]).get_uint(self.prefix_len);
+        if len > self.limit() {
+            return Err(RemoteError::FrameTooLarge(len));
+        }
+
+        let len = len as usize;
+        if src.len() < self.prefix_len + len {
+            src.reserve(self.prefix_len + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(self.prefix_len);
+        Ok(Some(src.split_to(len)))
+    }
+}
+
+#[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+impl Default for LengthDelimited {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`Codec`] that writes each message as a line of JSON.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Json {
+    max_frame_size: usize,
+    // How far into the buffer we already searched for a newline.
+    next_index: usize,
+}
+
+#[cfg(feature = "json")]
+impl Json {
+    /// Creates a JSON codec that accepts lines of up to [`DEFAULT_MAX_FRAME_SIZE`] bytes.
+    pub fn new() -> Self {
+        Self {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            next_index: 0,
+        }
+    }
+
+    /// Sets the longest line that may be sent or received.
+    ///
+    /// A peer that sends more than `max_frame_size` bytes without a newline is rejected with
+    /// [`RemoteError::FrameTooLarge`] instead of being buffered.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_frame_size` - The maximum length of a line, in bytes, not counting the newline.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+}
+
+#[cfg(feature = "json")]
+impl Default for Json {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "json")]
+impl Codec for Json {
+    fn encode<T: Serialize>(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), RemoteError> {
+        let start = dst.len();
+        serde_json::to_writer(dst.writer(), item).map_err(|e| RemoteError::Encode(e.into()))?;
+
+        let len = dst.len() - start;
+        if len > self.max_frame_size {
+            dst.truncate(start);
+            return Err(RemoteError::FrameTooLarge(len as u64));
+        }
+
+        dst.put_u8(b'\n');
+        Ok(())
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>, RemoteError> {
+        let Some(offset) = src[self.next_index..].iter().position(|b| *b == b'\n') else {
+            self.next_index = src.len();
+            if src.len() > self.max_frame_size {
+                return Err(RemoteError::FrameTooLarge(src.len() as u64));
+            }
+            return Ok(None);
+        };
+
+        if self.next_index + offset > self.max_frame_size {
+            return Err(RemoteError::FrameTooLarge(
+                (self.next_index + offset) as u64,
+            ));
+        }
+
+        let line = src.split_to(self.next_index + offset + 1);
+        self.next_index = 0;
+
+        serde_json::from_slice(&line[..line.len() - 1])
+            .map(Some)
+            .map_err(|e| RemoteError::Decode(e.into()))
+    }
+}
+
+/// A [`Codec`] that writes each message as a length-prefixed frame of bincode.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Default)]
+pub struct Bincode {
+    framing: LengthDelimited,
+}
+
+#[cfg(feature = "bincode")]
+impl Bincode {
+    /// Creates a bincode codec with the default [`LengthDelimited`] framing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the framing of the codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `framing` - The length prefix and maximum frame size to use.
+    pub fn framing(mut self, framing: LengthDelimited) -> Self {
+        self.framing = framing;
+        self
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl Codec for Bincode {
+    fn encode<T: Serialize>(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), RemoteError> {
+        self.framing.encode(dst, |w| {
+            bincode::serialize_into(w, item).map_err(|e| RemoteError::Encode(e.into()))
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>, RemoteError> {
+        let Some(frame) = self.framing.decode(src)? else {
+            return Ok(None);
+        };
+
+        bincode::deserialize(&frame)
+            .map(Some)
+            .map_err(|e| RemoteError::Decode(e.into()))
+    }
+}
+
+/// A [`Codec`] that writes each message as a length-prefixed frame of MessagePack.
+///
+/// Structs are encoded as maps so that both ends only need to agree on field names.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Default)]
+pub struct MessagePack {
+    framing: LengthDelimited,
+}
+
+#[cfg(feature = "msgpack")]
+impl MessagePack {
+    /// Creates a MessagePack codec with the default [`LengthDelimited`] framing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the framing of the codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `framing` - The length prefix and maximum frame size to use.
+    pub fn framing(mut self, framing: LengthDelimited) -> Self {
+        self.framing = framing;
+        self
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    fn encode<T: Serialize>(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), RemoteError> {
+        self.framing.encode(dst, |mut w| {
+            rmp_serde::encode::write_named(&mut w, item).map_err(|e| RemoteError::Encode(e.into()))
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>, RemoteError> {
+        let Some(frame) = self.framing.decode(src)? else {
+            return Ok(None);
+        };
+
+        rmp_serde::from_slice(&frame)
+            .map(Some)
+            .map_err(|e| RemoteError::Decode(e.into()))
+    }
+}
+
+/// A [`Codec`] that writes each message as a length-prefixed frame of CBOR.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Default)]
+pub struct Cbor {
+    framing: LengthDelimited,
+}
+
+#[cfg(feature = "cbor")]
+impl Cbor {
+    /// Creates a CBOR codec with the default [`LengthDelimited`] framing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the framing of the codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `framing` - The length prefix and maximum frame size to use.
+    pub fn framing(mut self, framing: LengthDelimited) -> Self {
+        self.framing = framing;
+        self
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    fn encode<T: Serialize>(&mut self, item: &T, dst: &mut BytesMut) -> Result<(), RemoteError> {
+        self.framing.encode(dst, |w| {
+            ciborium::into_writer(item, w).map_err(|e| RemoteError::Encode(e.into()))
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>, RemoteError> {
+        let Some(frame) = self.framing.decode(src)? else {
+            return Ok(None);
+        };
+
+        ciborium::from_reader(&frame[..])
+            .map(Some)
+            .map_err(|e| RemoteError::Decode(e.into()))
+    }
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "json",
+        feature = "bincode",
+        feature = "msgpack",
+        feature = "cbor"
+    )
+))]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::remote::bridge;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Data(Vec<u8>),
+        Control { code: u16 },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        id: u64,
+        text: String,
+        kind: Kind,
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message {
+                id: 1,
+                text: "line\nbreak".to_string(),
+                kind: Kind::Data(vec![0, 10, 255]),
+            },
+            Message {
+                id: u64::MAX,
+                text: String::new(),
+                kind: Kind::Control { code: 7 },
+            },
+        ]
+    }
+
+    fn check_round_trip<C: Codec>(mut codec: C) {
+        let mut buf = BytesMut::new();
+        for message in messages() {
+            codec.encode(&message, &mut buf).unwrap();
+        }
+
+        for message in messages() {
+            assert_eq!(codec.decode::<Message>(&mut buf).unwrap(), Some(message));
+        }
+        assert_eq!(codec.decode::<Message>(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+    }
+
+    fn check_truncated_frame<C: Codec>(mut codec: C) {
+        let mut frame = BytesMut::new();
+        codec.encode(&messages()[0], &mut frame).unwrap();
+
+        // Feed the frame a byte at a time: no prefix of it may decode.
+        let mut buf = BytesMut::new();
+        for (i, byte) in frame.iter().enumerate() {
+            assert_eq!(
+                codec.decode::<Message>(&mut buf).unwrap(),
+                None,
+                "decoded after {} bytes",
+                i
+            );
+            buf.put_u8(*byte);
+        }
+        assert_eq!(
+            codec.decode::<Message>(&mut buf).unwrap(),
+            Some(messages()[0].clone())
+        );
+    }
+
+    async fn check_truncated_stream<C: Codec + Clone + Send + 'static>(mut codec: C) {
+        let mut frame = BytesMut::new();
+        codec.encode(&messages()[0], &mut frame).unwrap();
+
+        let (a, mut b) = duplex(1024);
+        let (mut chan, handle) = bridge::<Message, Message, _, _>(a, codec, 4);
+
+        b.write_all(&frame[..frame.len() - 1]).await.unwrap();
+        b.shutdown().await.unwrap();
+
+        assert_eq!(chan.recv().await, None);
+        drop(chan);
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::Truncated
+        ));
+    }
+
+    /// Checks that a codec limited to 16 bytes refuses to encode a larger message and rejects
+    /// `hostile`, which announces a huge frame, without buffering it.
+    async fn check_oversized_frame<C: Codec + Clone + Send + 'static>(
+        mut codec: C,
+        hostile: &[u8],
+    ) {
+        let mut buf = BytesMut::new();
+        assert!(matches!(
+            codec.encode(&messages()[0], &mut buf),
+            Err(RemoteError::FrameTooLarge(_))
+        ));
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from(hostile);
+        assert!(matches!(
+            codec.clone().decode::<Message>(&mut buf),
+            Err(RemoteError::FrameTooLarge(_))
+        ));
+        assert!(buf.capacity() < 1024);
+
+        let (a, mut b) = duplex(1024);
+        let (mut chan, handle) = bridge::<Message, Message, _, _>(a, codec, 4);
+
+        b.write_all(hostile).await.unwrap();
+
+        assert_eq!(chan.recv().await, None);
+        assert!(matches!(
+            chan.remote_error().as_deref(),
+            Some(RemoteError::FrameTooLarge(_))
+        ));
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::FrameTooLarge(_)
+        ));
+
+        // The bridge dropped its end of the connection.
+        let mut rest = Vec::new();
+        b.read_to_end(&mut rest).await.unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_json() {
+        check_round_trip(Json::new());
+        check_truncated_frame(Json::new());
+        check_truncated_stream(Json::new()).await;
+        check_oversized_frame(Json::new().max_frame_size(16), &[b' '; 17]).await;
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn test_bincode() {
+        let small = LengthDelimited::new().max_frame_size(16);
+
+        check_round_trip(Bincode::new());
+        check_truncated_frame(Bincode::new());
+        check_truncated_stream(Bincode::new()).await;
+        check_oversized_frame(Bincode::new().framing(small), &[0xff; 4]).await;
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack() {
+        let small = LengthDelimited::new().max_frame_size(16);
+
+        check_round_trip(MessagePack::new());
+        check_truncated_frame(MessagePack::new());
+        check_truncated_stream(MessagePack::new()).await;
+        check_oversized_frame(MessagePack::new().framing(small), &[0xff; 4]).await;
+    }
+
+    #[cfg(feature = "cbor")]
+    #[tokio::test]
+    async fn test_cbor() {
+        let small = LengthDelimited::new().max_frame_size(16);
+
+        check_round_trip(Cbor::new());
+        check_truncated_frame(Cbor::new());
+        check_truncated_stream(Cbor::new()).await;
+        check_oversized_frame(Cbor::new().framing(small), &[0xff; 4]).await;
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn test_prefix_len() {
+        // A one-byte prefix cannot describe frames longer than 255 bytes.
+        let framing = LengthDelimited::new().prefix_len(1);
+        let mut codec = Bincode::new().framing(framing);
+
+        check_round_trip(codec.clone());
+        check_truncated_frame(codec.clone());
+
+        let mut buf = BytesMut::new();
+        codec.encode(&vec![0u8; 200], &mut buf).unwrap();
+        assert_eq!(buf[0] as usize, buf.len() - 1);
+        assert!(matches!(
+            codec.encode(&vec![0u8; 300], &mut buf),
+            Err(RemoteError::FrameTooLarge(_))
+        ));
+
+        let framing = LengthDelimited::new().prefix_len(8);
+        check_round_trip(Bincode::new().framing(framing));
+
+        let mut buf = BytesMut::from(&[0xff; 8][..]);
+        assert!(matches!(
+            Bincode::new().framing(framing).decode::<Message>(&mut buf),
+            Err(RemoteError::FrameTooLarge(_))
+        ));
+    }
+}
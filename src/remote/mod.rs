@@ -0,0 +1,460 @@
+//! Bridging a [`Channel`] over a byte stream so that the peer can live in another process.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::{channel, Channel, ChannelReceiver, ChannelSender};
+
+mod codec;
+#[cfg(feature = "time")]
+mod reconnect;
+#[cfg(feature = "net")]
+pub mod tcp;
+#[cfg(all(feature = "net", unix))]
+pub mod unix;
+
+#[cfg(feature = "bincode")]
+pub use codec::Bincode;
+#[cfg(feature = "cbor")]
+pub use codec::Cbor;
+#[cfg(feature = "json")]
+pub use codec::Json;
+#[cfg(any(feature = "bincode", feature = "msgpack", feature = "cbor"))]
+pub use codec::LengthDelimited;
+#[cfg(feature = "msgpack")]
+pub use codec::MessagePack;
+pub use codec::{Codec, DEFAULT_MAX_FRAME_SIZE};
+#[cfg(feature = "time")]
+pub use reconnect::{ConnectionState, Reconnect};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A handle to the tasks driving a [`bridge`], which resolves to the first error encountered
+/// once both directions have finished.
+pub type BridgeHandle = JoinHandle<Result<(), Arc<RemoteError>>>;
+
+/// Error that ends a bridge started by [`bridge`].
+#[derive(Debug)]
+pub enum RemoteError {
+    /// Reading from or writing to the stream failed.
+    Io(io::Error),
+    /// A message could not be encoded.
+    Encode(BoxError),
+    /// A frame received from the stream could not be decoded.
+    Decode(BoxError),
+    /// The stream ended in the middle of a frame.
+    Truncated,
+    /// A frame of the given length exceeded the maximum frame size of the codec.
+    FrameTooLarge(u64),
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Io(e) => write!(f, "transport error: {}", e),
+            RemoteError::Encode(e) => write!(f, "failed to encode message: {}", e),
+            RemoteError::Decode(e) => write!(f, "failed to decode frame: {}", e),
+            RemoteError::Truncated => write!(f, "stream ended in the middle of a frame"),
+            RemoteError::FrameTooLarge(len) => {
+                write!(f, "frame of {} bytes exceeds the maximum frame size", len)
+            }
+        }
+    }
+}
+
+impl Error for RemoteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RemoteError::Io(e) => Some(e),
+            RemoteError::Encode(e) | RemoteError::Decode(e) => Some(&**e),
+            RemoteError::Truncated | RemoteError::FrameTooLarge(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for RemoteError {
+    fn from(e: io::Error) -> Self {
+        RemoteError::Io(e)
+    }
+}
+
+/// Runs a [`Channel`] over a byte stream such as a TCP connection, a Unix socket or
+/// [`tokio::io::duplex`].
+///
+/// Messages sent on the returned channel are encoded with `codec` and written to `io`, and
+/// frames read from `io` are decoded and received on the returned channel. The peer is
+/// expected to run a bridge with the same codec and swapped message types.
+///
+/// When the stream reaches end-of-file the returned channel stops receiving once every
+/// decoded message has been received. When the returned channel stops sending, either through
+/// [`Channel::close_send`] or by being dropped, the write side of the stream is shut down.
+///
+/// The returned handle completes once both directions have finished. An error in either
+/// direction, such as a message that cannot be encoded or a frame larger than the codec allows,
+/// ends the other one and closes the stream, and the handle completes with that error. It is
+/// also reported by [`Channel::remote_error`] once the returned channel stops receiving.
+///
+/// This spawns tasks that drive the stream, so it must be called from within a Tokio runtime.
+///
+/// # Arguments
+///
+/// * `io` - The byte stream to run the channel over.
+/// * `codec` - The codec used to frame messages on the stream.
+/// * `buffer_size` - The size of the buffer for each direction of the returned channel.
+///
+/// # Returns
+///
+/// * `(Channel<S, R>, BridgeHandle)` - Returns the channel and a handle to the tasks driving the
+///   stream.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use tokio_bichannel::remote::{bridge, Json};
+///
+/// let (a, b) = tokio::io::duplex(1024);
+///
+/// let (mut client, _) = bridge::<String, u32, _, _>(a, Json::new(), 10);
+/// let (mut server, _) = bridge::<u32, String, _, _>(b, Json::new(), 10);
+///
+/// client.send("Hello".to_string()).await.unwrap();
+/// assert_eq!(server.recv().await.unwrap(), "Hello");
+///
+/// server.send(5).await.unwrap();
+/// assert_eq!(client.recv().await.unwrap(), 5);
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+pub fn bridge<S, R, T, C>(io: T, codec: C, buffer_size: usize) -> (Channel<S, R>, BridgeHandle)
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+    T: AsyncRead + AsyncWrite + Send + 'static,
+    C: Codec + Clone + Send + 'static,
+{
+    let (local, remote) = channel::<S, R>(buffer_size);
+    let (tx, rx) = remote.split();
+    let (reader, writer) = tokio::io::split(io);
+
+    let state = tx.state.clone();
+    let handle = tokio::spawn(async move {
+        let mut tasks = JoinSet::new();
+        tasks.spawn(read_frames(reader, codec.clone(), tx));
+        tasks.spawn(async move { write_frames(writer, codec, rx).await.map_err(Arc::new) });
+
+        while let Some(result) = tasks.join_next().await {
+            let result =
+                result.unwrap_or_else(|e| Err(Arc::new(RemoteError::Io(io::Error::other(e)))));
+
+            // Stopping the other direction drops both halves of the stream, which closes it.
+            if let Err(e) = result {
+                let _ = state.remote_error.set(e.clone());
+                tasks.shutdown().await;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    });
+
+    (local, handle)
+}
+
+/// Reads frames into `tx` until the stream ends, recording any error on the direction before
+/// the receiving channel sees it close.
+async fn read_frames<T, C, R>(
+    reader: ReadHalf<T>,
+    codec: C,
+    tx: ChannelSender<R>,
+) -> Result<(), Arc<RemoteError>>
+where
+    T: AsyncRead,
+    C: Codec,
+    R: DeserializeOwned,
+{
+    let result = read_into(reader, codec, &tx).await.map_err(Arc::new);
+    if let Err(e) = &result {
+        let _ = tx.state.remote_error.set(e.clone());
+    }
+    result
+}
+
+async fn read_into<T, C, R>(
+    mut reader: ReadHalf<T>,
+    mut codec: C,
+    tx: &ChannelSender<R>,
+) -> Result<(), RemoteError>
+where
+    T: AsyncRead,
+    C: Codec,
+    R: DeserializeOwned,
+{
+    let mut buf = BytesMut::new();
+
+    loop {
+        while let Some(r) = codec.decode(&mut buf)? {
+            if tx.send(r).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        if reader.read_buf(&mut buf).await? == 0 {
+            return if buf.is_empty() {
+                Ok(())
+            } else {
+                Err(RemoteError::Truncated)
+            };
+        }
+    }
+}
+
+async fn write_frames<T, C, S>(
+    mut writer: WriteHalf<T>,
+    mut codec: C,
+    mut rx: ChannelReceiver<S>,
+) -> Result<(), RemoteError>
+where
+    T: AsyncWrite,
+    C: Codec,
+    S: Serialize,
+{
+    let mut buf = BytesMut::new();
+
+    while let Some(s) = rx.recv().await {
+        codec.encode(&s, &mut buf)?;
+
+        // Batch up whatever else is already queued into the same write.
+        while let Ok(s) = rx.try_recv() {
+            codec.encode(&s, &mut buf)?;
+        }
+
+        writer.write_all_buf(&mut buf).await?;
+        writer.flush().await?;
+    }
+
+    writer.shutdown().await?;
+    Ok(())
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Returns the error that made a [`bridge`] stop receiving from its stream, if any.
+    ///
+    /// See [`Channel::remote_error`].
+    pub fn remote_error(&self) -> Option<Arc<RemoteError>> {
+        self.state.remote_error.get().cloned()
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Returns the error that made a channel created by [`bridge`] stop receiving from its
+    /// stream, such as [`RemoteError::FrameTooLarge`].
+    ///
+    /// The error is recorded before `recv` returns `None`, so this tells a broken connection
+    /// apart from a peer that closed it cleanly, which leaves this `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "json")]
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use tokio::io::AsyncWriteExt;
+    /// use tokio_bichannel::remote::{bridge, Json, RemoteError};
+    ///
+    /// let (a, mut b) = tokio::io::duplex(1024);
+    /// let (mut chan, _) = bridge::<u32, u32, _, _>(a, Json::new(), 10);
+    ///
+    /// b.write_all(b"not json\n").await.unwrap();
+    ///
+    /// assert!(chan.recv().await.is_none());
+    /// assert!(matches!(chan.remote_error().as_deref(), Some(RemoteError::Decode(_))));
+    /// # }
+    /// # #[cfg(not(feature = "json"))]
+    /// # fn main() {}
+    /// ```
+    pub fn remote_error(&self) -> Option<Arc<RemoteError>> {
+        self.receiver.remote_error()
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Serialize};
+    use tokio::io::{duplex, AsyncWriteExt};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Ping(u32),
+        Text(String),
+    }
+
+    /// A message the codec fails to encode.
+    struct Unencodable;
+
+    impl Serialize for Unencodable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("cannot be encoded"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bridge_round_trip() {
+        let (a, b) = duplex(64);
+
+        let (mut left, left_handle) = bridge::<Message, Message, _, _>(a, Json::new(), 4);
+        let (mut right, right_handle) = bridge::<Message, Message, _, _>(b, Json::new(), 4);
+
+        for i in 0..100 {
+            left.send(Message::Ping(i)).await.unwrap();
+            assert_eq!(right.recv().await.unwrap(), Message::Ping(i));
+        }
+
+        right.send(Message::Text("bye".to_string())).await.unwrap();
+        assert_eq!(left.recv().await.unwrap(), Message::Text("bye".to_string()));
+
+        left.close_send();
+        assert!(right.recv().await.is_none());
+
+        right
+            .send(Message::Text("still open".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            left.recv().await.unwrap(),
+            Message::Text("still open".to_string())
+        );
+
+        drop(right);
+        assert!(left.recv().await.is_none());
+
+        left_handle.await.unwrap().unwrap();
+        right_handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpc_over_bridge() {
+        use crate::rpc::{RpcClient, RpcServer};
+
+        let (a, b) = duplex(64);
+
+        let client = RpcClient::<u32, u32>::new(bridge(a, Json::new(), 4).0);
+        let mut server = RpcServer::<u32, u32>::new(bridge(b, Json::new(), 4).0);
+
+        tokio::spawn(async move {
+            while let Some((n, responder)) = server.recv().await {
+                responder.send(n + 1);
+            }
+        });
+
+        assert_eq!(client.call(1).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_decode_error_closes_connection() {
+        let (a, mut b) = duplex(64);
+
+        let (mut chan, handle) = bridge::<u32, u32, _, _>(a, Json::new(), 4);
+
+        b.write_all(b"1\n\"not a number\"\n2\n").await.unwrap();
+
+        assert_eq!(chan.recv().await, Some(1));
+        assert_eq!(chan.recv().await, None);
+        assert!(matches!(
+            chan.remote_error().as_deref(),
+            Some(RemoteError::Decode(_))
+        ));
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::Decode(_)
+        ));
+
+        let mut rest = Vec::new();
+        b.read_to_end(&mut rest).await.unwrap();
+        assert!(chan.send(3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encode_error_closes_connection() {
+        let (a, mut b) = duplex(64);
+
+        let (mut chan, handle) = bridge::<Unencodable, u32, _, _>(a, Json::new(), 4);
+
+        chan.send(Unencodable).await.unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), handle).await;
+        assert!(matches!(
+            *result.unwrap().unwrap().unwrap_err(),
+            RemoteError::Encode(_)
+        ));
+
+        // Both directions end and the stream is closed, although the peer never closed it.
+        assert_eq!(chan.recv().await, None);
+        assert!(matches!(
+            chan.remote_error().as_deref(),
+            Some(RemoteError::Encode(_))
+        ));
+        assert!(chan.send(Unencodable).await.is_err());
+
+        let mut rest = Vec::new();
+        b.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_frame_too_large_reaches_receiver() {
+        let (a, b) = duplex(64);
+
+        let (mut chan, handle) = bridge::<u32, String, _, _>(a, Json::new().max_frame_size(16), 4);
+        let (peer, _) = bridge::<String, u32, _, _>(b, Json::new(), 4);
+
+        peer.send("x".repeat(64)).await.unwrap();
+
+        assert_eq!(chan.recv().await, None);
+        assert!(matches!(
+            chan.remote_error().as_deref(),
+            Some(RemoteError::FrameTooLarge(_))
+        ));
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::FrameTooLarge(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame() {
+        let (a, mut b) = duplex(64);
+
+        let (mut chan, handle) = bridge::<u32, u32, _, _>(a, Json::new(), 4);
+
+        b.write_all(b"1\n23").await.unwrap();
+        b.shutdown().await.unwrap();
+
+        assert_eq!(chan.recv().await, Some(1));
+        assert_eq!(chan.recv().await, None);
+        assert!(matches!(
+            chan.remote_error().as_deref(),
+            Some(RemoteError::Truncated)
+        ));
+        drop(chan);
+
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::Truncated
+        ));
+    }
+}
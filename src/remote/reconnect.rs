@@ -0,0 +1,591 @@
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+
+use super::{bridge, Codec, RemoteError};
+use crate::{channel, Channel, ChannelReceiver, ChannelSender};
+
+/// The state of the connection behind a channel started with [`Reconnect::start`].
+#[derive(Debug, Clone)]
+pub enum ConnectionState {
+    /// Dialing, with `attempt` counting from 1 since the start or since the last connection
+    /// was lost.
+    Connecting {
+        /// The number of the current attempt.
+        attempt: u32,
+    },
+    /// Connected; messages flow to and from the peer.
+    Connected,
+    /// The connection was lost and is being dialed again. Messages handed to it that the peer
+    /// had not received yet were lost with it.
+    ///
+    /// This lasts until the first dial succeeds or fails, so it is not missed while the
+    /// connection comes straight back; further attempts are reported as `Connecting`.
+    Disconnected {
+        /// The error that ended the connection, or `None` if the peer closed it.
+        reason: Option<Arc<RemoteError>>,
+    },
+    /// The application closed both halves of its channel, so no more connections are made.
+    Closed,
+}
+
+/// A remote channel that survives its connection, dialing again whenever the connection is
+/// lost.
+///
+/// The application keeps using the same [`Channel`] across connections. While disconnected,
+/// messages sent on it are held in its buffer and delivered in order once a new connection is
+/// up; when the buffer is full, `send` waits as it would for a slow peer.
+///
+/// Delivery is at most once. Messages are handed from the buffer to the current connection as
+/// soon as it can take them, and the ones it has not written to the peer yet, or that the peer
+/// had not received, are lost when the connection fails. Every such failure is reported as
+/// [`ConnectionState::Disconnected`]; send acknowledgements on top of the channel, as
+/// [`Reliable`] does, if messages must survive it.
+///
+/// Failed dials are retried with exponential backoff, starting again from the initial delay
+/// after every successful connection.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "json", feature = "net"))]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use tokio::net::TcpStream;
+/// use tokio_bichannel::remote::{tcp, ConnectionState, Json, Reconnect};
+///
+/// let listener = tcp::Listener::bind("127.0.0.1:0", Json::new(), 10).await.unwrap();
+/// let addr = listener.local_addr().unwrap();
+///
+/// tokio::spawn(async move {
+///     let (mut chan, _) = listener.accept::<String, String>().await.unwrap();
+///     while let Some(msg) = chan.recv().await {
+///         chan.send(msg).await.unwrap();
+///     }
+/// });
+///
+/// let (mut chan, mut state) = Reconnect::new(move || TcpStream::connect(addr), Json::new())
+///     .start::<String, String, _, _>();
+///
+/// chan.send("echo".to_string()).await.unwrap();
+/// assert_eq!(chan.recv().await.unwrap(), "echo");
+/// assert!(matches!(*state.borrow(), ConnectionState::Connected));
+///
+/// drop(chan);
+/// state
+///     .wait_for(|state| matches!(state, ConnectionState::Closed))
+///     .await
+///     .unwrap();
+/// # }
+/// # #[cfg(not(all(feature = "json", feature = "net")))]
+/// # fn main() {}
+/// ```
+///
+/// [`Reliable`]: crate::reliable::Reliable
+#[derive(Debug, Clone)]
+pub struct Reconnect<D, C> {
+    dial: D,
+    codec: C,
+    buffer_size: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<D, C> Reconnect<D, C> {
+    /// Creates a reconnecting channel configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `dial` - Opens a new byte stream to the peer, for example
+    ///   `move || TcpStream::connect(addr)`.
+    /// * `codec` - The codec used on every connection.
+    ///
+    /// # Returns
+    ///
+    /// * `Reconnect<D, C>` - Returns the configuration.
+    pub fn new(dial: D, codec: C) -> Self {
+        Self {
+            dial,
+            codec,
+            buffer_size: 100,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets how many messages each direction of the application's channel buffers, which is
+    /// also how many outgoing messages are held while disconnected. Defaults to 100.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer_size` - The size of the buffer for each direction.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Sets the delay before retrying a failed dial, which doubles with every failure up to
+    /// `max`. Defaults to 100 milliseconds, up to 30 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial` - The delay after the first failed dial.
+    /// * `max` - The longest delay between two dials.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Starts dialing and returns the application's channel.
+    ///
+    /// This spawns a task that manages the connection, so it must be called from within a
+    /// Tokio runtime. The task stops once the application has closed or dropped both halves of
+    /// the channel, even in the middle of dialing, and once every message it sent before has
+    /// been handed to a connection.
+    ///
+    /// # Returns
+    ///
+    /// * `(Channel<S, R>, watch::Receiver<ConnectionState>)` - Returns the channel and a receiver of connection state changes.
+    pub fn start<S, R, F, T>(self) -> (Channel<S, R>, watch::Receiver<ConnectionState>)
+    where
+        S: Serialize + Send + 'static,
+        R: DeserializeOwned + Send + 'static,
+        D: FnMut() -> F + Send + 'static,
+        F: Future<Output = io::Result<T>> + Send + 'static,
+        T: AsyncRead + AsyncWrite + Send + 'static,
+        C: Codec + Clone + Send + 'static,
+    {
+        let (app, local) = channel::<S, R>(self.buffer_size);
+        let (state, state_rx) = watch::channel(ConnectionState::Connecting { attempt: 1 });
+        let (to_app, from_app) = local.split();
+
+        tokio::spawn(self.run(from_app, to_app, state));
+
+        (app, state_rx)
+    }
+
+    async fn run<S, R, F, T>(
+        mut self,
+        mut from_app: ChannelReceiver<S>,
+        to_app: ChannelSender<R>,
+        state: watch::Sender<ConnectionState>,
+    ) where
+        S: Serialize + Send + 'static,
+        R: DeserializeOwned + Send + 'static,
+        D: FnMut() -> F,
+        F: Future<Output = io::Result<T>>,
+        T: AsyncRead + AsyncWrite + Send + 'static,
+        C: Codec + Clone + Send + 'static,
+    {
+        let mut sending = true;
+        let mut receiving = true;
+        // A message taken from the application while dialing, sent first once connected.
+        let mut held = None;
+
+        'connections: loop {
+            let mut backoff = self.initial_backoff;
+            let mut attempt = 1;
+
+            let io = loop {
+                if attempt > 1 || !matches!(*state.borrow(), ConnectionState::Disconnected { .. }) {
+                    state.send_replace(ConnectionState::Connecting { attempt });
+                }
+
+                let dialed = {
+                    let mut app_closed = pin!(to_app.closed());
+                    let mut next = pin!(from_app.recv());
+                    let mut dialing = pin!(async {
+                        let result = (self.dial)().await;
+                        if result.is_err() {
+                            tokio::time::sleep(backoff).await;
+                        }
+                        result
+                    });
+
+                    // Watches the application while dialing, to give up as soon as it is gone.
+                    poll_fn(|cx| {
+                        if receiving && app_closed.as_mut().poll(cx).is_ready() {
+                            receiving = false;
+                        }
+                        if sending && held.is_none() {
+                            if let Poll::Ready(next) = next.as_mut().poll(cx) {
+                                match next {
+                                    Some(s) => held = Some(s),
+                                    None => sending = false,
+                                }
+                            }
+                        }
+                        if !sending && !receiving {
+                            return Poll::Ready(None);
+                        }
+                        dialing.as_mut().poll(cx).map(Some)
+                    })
+                    .await
+                };
+
+                match dialed {
+                    None => break 'connections,
+                    Some(Ok(io)) => break io,
+                    Some(Err(_e)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!(attempt, error = %_e, "failed to dial remote channel");
+
+                        backoff = (backoff * 2).min(self.max_backoff);
+                        attempt += 1;
+                    }
+                }
+            };
+
+            let (conn, bridged) = bridge::<S, R, _, _>(io, self.codec.clone(), self.buffer_size);
+            let (conn_tx, conn_rx) = conn.split();
+            state.send_replace(ConnectionState::Connected);
+
+            {
+                let mut outgoing =
+                    pin!(forward_outgoing(&mut from_app, &mut held, conn_tx, sending));
+                let mut incoming = pin!(forward_incoming(conn_rx, &to_app, receiving));
+
+                let first = poll_fn(|cx| {
+                    if let Poll::Ready(leg) = incoming.as_mut().poll(cx) {
+                        return Poll::Ready(First::Incoming(leg));
+                    }
+                    outgoing.as_mut().poll(cx).map(First::Outgoing)
+                })
+                .await;
+
+                // Neither leg takes a message before it has room for it, so once the connection
+                // is lost in one direction the other leg is dropped without losing anything.
+                match first {
+                    First::Incoming(Leg::Lost) | First::Outgoing(Leg::Lost) => {}
+                    First::Incoming(Leg::Done) => {
+                        receiving = false;
+                        sending &= outgoing.await == Leg::Lost;
+                    }
+                    First::Outgoing(Leg::Done) => {
+                        sending = false;
+                        receiving &= incoming.await == Leg::Lost;
+                    }
+                }
+            }
+
+            if !sending && !receiving {
+                break;
+            }
+
+            // Both legs are gone, so the bridge winds down and reports why.
+            let reason = match bridged.await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e),
+                Err(e) => Some(Arc::new(RemoteError::Io(io::Error::other(e)))),
+            };
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(reason = ?reason, "lost remote channel connection");
+
+            state.send_replace(ConnectionState::Disconnected { reason });
+        }
+
+        state.send_replace(ConnectionState::Closed);
+    }
+}
+
+/// How forwarding in one direction of a connection ended.
+#[derive(Debug, PartialEq, Eq)]
+enum Leg {
+    /// The application closed its end of the direction.
+    Done,
+    /// The connection was lost.
+    Lost,
+}
+
+enum First {
+    Outgoing(Leg),
+    Incoming(Leg),
+}
+
+async fn forward_outgoing<S>(
+    from_app: &mut ChannelReceiver<S>,
+    held: &mut Option<S>,
+    conn: ChannelSender<S>,
+    sending: bool,
+) -> Leg {
+    if !sending {
+        return Leg::Done;
+    }
+
+    loop {
+        // Reserving first keeps the message queued in the application's channel if the
+        // connection is lost meanwhile.
+        let Ok(permit) = conn.reserve().await else {
+            return Leg::Lost;
+        };
+        let next = match held.take() {
+            Some(s) => Ok(Some(s)),
+            None => {
+                // Watches the connection while waiting, so losing it is noticed before the
+                // next message is taken.
+                let mut lost = pin!(conn.closed());
+                let mut recv = pin!(from_app.recv());
+
+                poll_fn(|cx| {
+                    if lost.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready(Err(Leg::Lost));
+                    }
+                    recv.as_mut().poll(cx).map(Ok)
+                })
+                .await
+            }
+        };
+        match next {
+            Ok(Some(s)) => permit.send(s),
+            Ok(None) => return Leg::Done,
+            Err(leg) => return leg,
+        }
+    }
+}
+
+async fn forward_incoming<R>(
+    mut conn: ChannelReceiver<R>,
+    to_app: &ChannelSender<R>,
+    receiving: bool,
+) -> Leg {
+    loop {
+        // Reserving first keeps the message queued in the connection if this leg is dropped
+        // because the other one was lost.
+        let permit = if receiving {
+            let Ok(permit) = to_app.reserve().await else {
+                return Leg::Done;
+            };
+            Some(permit)
+        } else {
+            None
+        };
+
+        let next = {
+            let mut app_closed = pin!(to_app.closed());
+            let mut recv = pin!(conn.recv());
+
+            poll_fn(|cx| {
+                if receiving && app_closed.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Err(Leg::Done));
+                }
+                recv.as_mut().poll(cx).map(|r| r.ok_or(Leg::Lost))
+            })
+            .await
+        };
+
+        match next {
+            Ok(r) => {
+                if let Some(permit) = permit {
+                    permit.send(r);
+                }
+            }
+            Err(leg) => return leg,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use bytes::BytesMut;
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::remote::Json;
+
+    type Dials = Arc<Mutex<VecDeque<io::Result<DuplexStream>>>>;
+
+    /// Starts a reconnecting channel whose dials are answered from `dials`, failing when it is
+    /// empty.
+    fn start<C>(dials: &Dials, codec: C) -> (Channel<u32, u32>, watch::Receiver<ConnectionState>)
+    where
+        C: Codec + Clone + Send + 'static,
+    {
+        let dials = dials.clone();
+        let dial = move || {
+            let next = dials.lock().unwrap().pop_front();
+            async move { next.unwrap_or_else(|| Err(io::ErrorKind::ConnectionRefused.into())) }
+        };
+
+        Reconnect::new(dial, codec)
+            .buffer_size(8)
+            .backoff(Duration::from_millis(1), Duration::from_millis(4))
+            .start()
+    }
+
+    fn peer(dials: &Dials) -> Channel<u32, u32> {
+        let (a, b) = duplex(1024);
+        dials.lock().unwrap().push_back(Ok(a));
+        bridge(b, Json::new(), 8).0
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let dials = Dials::default();
+        dials
+            .lock()
+            .unwrap()
+            .push_back(Err(io::ErrorKind::ConnectionRefused.into()));
+        let mut server = peer(&dials);
+        let (mut chan, mut state) = start(&dials, Json::new());
+
+        chan.send(1).await.unwrap();
+        assert_eq!(server.recv().await, Some(1));
+        server.send(2).await.unwrap();
+        assert_eq!(chan.recv().await, Some(2));
+        assert!(matches!(*state.borrow(), ConnectionState::Connected));
+
+        // The server goes away, and messages sent during the outage are held.
+        drop(server);
+        state
+            .wait_for(
+                |state| matches!(state, ConnectionState::Connecting { attempt } if *attempt >= 3),
+            )
+            .await
+            .unwrap();
+        for i in 3..8 {
+            chan.send(i).await.unwrap();
+        }
+
+        let mut server = peer(&dials);
+        for i in 3..8 {
+            assert_eq!(server.recv().await, Some(i));
+        }
+        server.send(8).await.unwrap();
+        assert_eq!(chan.recv().await, Some(8));
+        assert!(matches!(*state.borrow(), ConnectionState::Connected));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_reason() {
+        let (a, mut b) = duplex(1024);
+        let mut first = Some(a);
+        // Only the first dial succeeds, and the ones after never complete.
+        let dial = move || {
+            let io = first.take();
+            async move {
+                match io {
+                    Some(io) => Ok(io),
+                    None => std::future::pending().await,
+                }
+            }
+        };
+        let (_chan, mut state) = Reconnect::new(dial, Json::new()).start::<u32, u32, _, _>();
+
+        b.write_all(b"not json\n").await.unwrap();
+        let state = state
+            .wait_for(|state| matches!(state, ConnectionState::Disconnected { .. }))
+            .await
+            .unwrap()
+            .clone();
+        let ConnectionState::Disconnected { reason } = state else {
+            unreachable!();
+        };
+        assert!(matches!(reason.as_deref(), Some(RemoteError::Decode(_))));
+    }
+
+    /// Decodes like [`Json`], but fails to encode as many messages as `failures` holds.
+    #[derive(Clone)]
+    struct FailingEncode {
+        json: Json,
+        failures: Arc<AtomicUsize>,
+    }
+
+    impl Codec for FailingEncode {
+        fn encode<T: Serialize>(
+            &mut self,
+            item: &T,
+            dst: &mut BytesMut,
+        ) -> Result<(), RemoteError> {
+            let fail = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if fail {
+                return Err(RemoteError::Encode("encoding failed".into()));
+            }
+            self.json.encode(item, dst)
+        }
+
+        fn decode<T: DeserializeOwned>(
+            &mut self,
+            src: &mut BytesMut,
+        ) -> Result<Option<T>, RemoteError> {
+            self.json.decode(src)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_failure() {
+        let dials = Dials::default();
+        let mut first = peer(&dials);
+        let mut second = peer(&dials);
+        let failures = Arc::new(AtomicUsize::new(0));
+        let codec = FailingEncode {
+            json: Json::new(),
+            failures: failures.clone(),
+        };
+        let (chan, _state) = start(&dials, codec);
+
+        // Nothing is received, so forwarding to the application stalls on a full buffer.
+        for i in 0..20 {
+            first.send(i).await.unwrap();
+        }
+
+        // Only the write half fails, and the connection is still torn down and dialed again.
+        failures.store(1, Ordering::SeqCst);
+        chan.send(1).await.unwrap();
+        assert_eq!(first.recv().await, None);
+
+        chan.send(2).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), second.recv()).await;
+        assert_eq!(received.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_closed_while_dialing() {
+        let dial = || std::future::pending::<io::Result<DuplexStream>>();
+        let (chan, mut state) = Reconnect::new(dial, Json::new()).start::<u32, u32, _, _>();
+
+        drop(chan);
+        state
+            .wait_for(|state| matches!(state, ConnectionState::Closed))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let dials = Dials::default();
+        let mut server = peer(&dials);
+        let (mut chan, mut state) = start(&dials, Json::new());
+
+        chan.send(1).await.unwrap();
+        chan.close_send();
+        assert_eq!(server.recv().await, Some(1));
+        assert_eq!(server.recv().await, None);
+
+        // Still receiving, so the connection is kept.
+        server.send(2).await.unwrap();
+        assert_eq!(chan.recv().await, Some(2));
+
+        drop(chan);
+        state
+            .wait_for(|state| matches!(state, ConnectionState::Closed))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+//! Channels over TCP connections.
+
+use std::io;
+use std::net::SocketAddr;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::{bridge, BridgeHandle, Codec};
+use crate::Channel;
+
+/// A TCP listener that turns every accepted connection into a [`Channel`].
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use tokio_bichannel::remote::{tcp, Json};
+///
+/// let listener = tcp::Listener::bind("127.0.0.1:0", Json::new(), 10).await.unwrap();
+/// let addr = listener.local_addr().unwrap();
+///
+/// tokio::spawn(async move {
+///     let (mut chan, _) = listener.accept::<String, String>().await.unwrap();
+///     while let Some(msg) = chan.recv().await {
+///         chan.send(msg).await.unwrap();
+///     }
+/// });
+///
+/// let (mut chan, _) = tcp::connect::<String, String, _, _>(addr, Json::new(), 10).await.unwrap();
+/// chan.send("echo".to_string()).await.unwrap();
+/// assert_eq!(chan.recv().await.unwrap(), "echo");
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct Listener<C> {
+    listener: TcpListener,
+    codec: C,
+    buffer_size: usize,
+}
+
+impl<C> Listener<C>
+where
+    C: Codec + Clone + Send + 'static,
+{
+    /// Binds a listener to `addr`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on.
+    /// * `codec` - The codec used on every accepted connection.
+    /// * `buffer_size` - The size of the buffer for each direction of the accepted channels.
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<Listener<C>>` - Returns the listener, or an error if binding failed.
+    pub async fn bind<A: ToSocketAddrs>(addr: A, codec: C, buffer_size: usize) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            codec,
+            buffer_size,
+        })
+    }
+
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for a connection and bridges it to a [`Channel`].
+    ///
+    /// The connection is driven by its own tasks; if it fails, the channel stops receiving and
+    /// both [`Channel::remote_error`] and the returned handle report the reason, such as
+    /// [`RemoteError::FrameTooLarge`].
+    ///
+    /// [`RemoteError::FrameTooLarge`]: super::RemoteError::FrameTooLarge
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<(Channel<S, R>, BridgeHandle)>` - Returns the channel and a handle
+    ///   to the tasks driving the connection, or an error if accepting failed.
+    pub async fn accept<S, R>(&self) -> io::Result<(Channel<S, R>, BridgeHandle)>
+    where
+        S: Serialize + Send + 'static,
+        R: DeserializeOwned + Send + 'static,
+    {
+        let (stream, _) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+
+        Ok(bridge(stream, self.codec.clone(), self.buffer_size))
+    }
+}
+
+/// Connects to a [`Listener`] at `addr` and bridges the connection to a [`Channel`].
+///
+/// The connection is driven by its own tasks; if it fails, the channel stops receiving and
+/// both [`Channel::remote_error`] and the returned handle report the reason, such as
+/// [`RemoteError::FrameTooLarge`].
+///
+/// [`RemoteError::FrameTooLarge`]: super::RemoteError::FrameTooLarge
+///
+/// # Arguments
+///
+/// * `addr` - The address to connect to.
+/// * `codec` - The codec used on the connection.
+/// * `buffer_size` - The size of the buffer for each direction of the channel.
+///
+/// # Returns
+///
+/// * `io::Result<(Channel<S, R>, BridgeHandle)>` - Returns the channel and a handle to
+///   the tasks driving the connection, or an error if connecting failed.
+pub async fn connect<S, R, A, C>(
+    addr: A,
+    codec: C,
+    buffer_size: usize,
+) -> io::Result<(Channel<S, R>, BridgeHandle)>
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+    A: ToSocketAddrs,
+    C: Codec + Clone + Send + 'static,
+{
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+
+    Ok(bridge(stream, codec, buffer_size))
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::remote::{Json, RemoteError};
+
+    #[tokio::test]
+    async fn test_loopback() {
+        let listener = Listener::bind("127.0.0.1:0", Json::new(), 4).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut handles = Vec::new();
+            for _ in 0..3 {
+                let (mut chan, _) = listener.accept::<u32, u32>().await.unwrap();
+                handles.push(tokio::spawn(async move {
+                    while let Some(n) = chan.recv().await {
+                        chan.send(n * 2).await.unwrap();
+                    }
+                }));
+            }
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        });
+
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            clients.push(
+                connect::<u32, u32, _, _>(addr, Json::new(), 4)
+                    .await
+                    .unwrap()
+                    .0,
+            );
+        }
+
+        for (i, client) in clients.iter_mut().enumerate() {
+            client.send(i as u32).await.unwrap();
+        }
+        for (i, client) in clients.iter_mut().enumerate() {
+            assert_eq!(client.recv().await, Some(i as u32 * 2));
+        }
+
+        drop(clients);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_frame_too_large() {
+        let listener = Listener::bind("127.0.0.1:0", Json::new().max_frame_size(16), 4)
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (client, _) = connect::<String, String, _, _>(addr, Json::new(), 4)
+            .await
+            .unwrap();
+        let (mut server, handle) = listener.accept::<String, String>().await.unwrap();
+
+        client.send("x".repeat(64)).await.unwrap();
+        assert!(server.recv().await.is_none());
+        assert!(matches!(
+            server.remote_error().as_deref(),
+            Some(RemoteError::FrameTooLarge(64..))
+        ));
+        assert!(matches!(
+            *handle.await.unwrap().unwrap_err(),
+            RemoteError::FrameTooLarge(_)
+        ));
+    }
+}
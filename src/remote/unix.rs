@@ -0,0 +1,160 @@
+//! Channels over Unix domain socket connections.
+
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::net::{UnixListener, UnixStream};
+
+use super::{bridge, BridgeHandle, Codec};
+use crate::Channel;
+
+/// A Unix domain socket listener that turns every accepted connection into a [`Channel`].
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use tokio_bichannel::remote::{unix, Json};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("bichannel.sock");
+///
+/// let listener = unix::Listener::bind(&path, Json::new(), 10).await.unwrap();
+///
+/// tokio::spawn(async move {
+///     let (mut chan, _) = listener.accept::<String, String>().await.unwrap();
+///     while let Some(msg) = chan.recv().await {
+///         chan.send(msg).await.unwrap();
+///     }
+/// });
+///
+/// let (mut chan, _) = unix::connect::<String, String, _, _>(&path, Json::new(), 10).await.unwrap();
+/// chan.send("echo".to_string()).await.unwrap();
+/// assert_eq!(chan.recv().await.unwrap(), "echo");
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct Listener<C> {
+    listener: UnixListener,
+    codec: C,
+    buffer_size: usize,
+}
+
+impl<C> Listener<C>
+where
+    C: Codec + Clone + Send + 'static,
+{
+    /// Binds a listener to the socket at `path`.
+    ///
+    /// This is `async` like [`tcp::Listener::bind`], so that both transports are set up the
+    /// same way, although binding a Unix socket never waits.
+    ///
+    /// [`tcp::Listener::bind`]: super::tcp::Listener::bind
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the socket to create.
+    /// * `codec` - The codec used on every accepted connection.
+    /// * `buffer_size` - The size of the buffer for each direction of the accepted channels.
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<Listener<C>>` - Returns the listener, or an error if binding failed.
+    pub async fn bind<P: AsRef<Path>>(path: P, codec: C, buffer_size: usize) -> io::Result<Self> {
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            codec,
+            buffer_size,
+        })
+    }
+
+    /// Waits for a connection and bridges it to a [`Channel`].
+    ///
+    /// The connection is driven by its own tasks; if it fails, the channel stops receiving and
+    /// both [`Channel::remote_error`] and the returned handle report the reason, such as
+    /// [`RemoteError::FrameTooLarge`].
+    ///
+    /// [`RemoteError::FrameTooLarge`]: super::RemoteError::FrameTooLarge
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<(Channel<S, R>, BridgeHandle)>` - Returns the channel and a handle
+    ///   to the tasks driving the connection, or an error if accepting failed.
+    pub async fn accept<S, R>(&self) -> io::Result<(Channel<S, R>, BridgeHandle)>
+    where
+        S: Serialize + Send + 'static,
+        R: DeserializeOwned + Send + 'static,
+    {
+        let (stream, _) = self.listener.accept().await?;
+        Ok(bridge(stream, self.codec.clone(), self.buffer_size))
+    }
+}
+
+/// Connects to a [`Listener`] at `path` and bridges the connection to a [`Channel`].
+///
+/// The connection is driven by its own tasks; if it fails, the channel stops receiving and
+/// both [`Channel::remote_error`] and the returned handle report the reason, such as
+/// [`RemoteError::FrameTooLarge`].
+///
+/// [`RemoteError::FrameTooLarge`]: super::RemoteError::FrameTooLarge
+///
+/// # Arguments
+///
+/// * `path` - The path of the socket to connect to.
+/// * `codec` - The codec used on the connection.
+/// * `buffer_size` - The size of the buffer for each direction of the channel.
+///
+/// # Returns
+///
+/// * `io::Result<(Channel<S, R>, BridgeHandle)>` - Returns the channel and a handle to
+///   the tasks driving the connection, or an error if connecting failed.
+pub async fn connect<S, R, P, C>(
+    path: P,
+    codec: C,
+    buffer_size: usize,
+) -> io::Result<(Channel<S, R>, BridgeHandle)>
+where
+    S: Serialize + Send + 'static,
+    R: DeserializeOwned + Send + 'static,
+    P: AsRef<Path>,
+    C: Codec + Clone + Send + 'static,
+{
+    let stream = UnixStream::connect(path).await?;
+    Ok(bridge(stream, codec, buffer_size))
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::remote::Json;
+
+    #[tokio::test]
+    async fn test_tempdir_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sock");
+
+        let listener = Listener::bind(&path, Json::new(), 4).await.unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut chan, _) = listener.accept::<String, String>().await.unwrap();
+            let msg = chan.recv().await.unwrap();
+            chan.send(msg.to_uppercase()).await.unwrap();
+            assert!(chan.recv().await.is_none());
+        });
+
+        let (mut chan, _) = connect::<String, String, _, _>(&path, Json::new(), 4)
+            .await
+            .unwrap();
+        chan.send("hello".to_string()).await.unwrap();
+        assert_eq!(chan.recv().await.unwrap(), "HELLO");
+
+        chan.close_send();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::sync::oneshot;
+
+use crate::{channel as bichannel, Channel, ChannelReceiver, ChannelSender, OwnedPermit};
+
+/// A request as it travels from an [`RpcClient`] to an [`RpcServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "remote", derive(serde::Serialize, serde::Deserialize))]
+pub struct Request<Req> {
+    /// The correlation ID the matching [`Response`] must carry.
+    pub id: u64,
+    /// The request payload.
+    pub body: Req,
+}
+
+/// A response as it travels from an [`RpcServer`] back to an [`RpcClient`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "remote", derive(serde::Serialize, serde::Deserialize))]
+pub struct Response<Resp> {
+    /// The correlation ID of the [`Request`] being answered.
+    pub id: u64,
+    /// The response payload, or `None` if the [`Responder`] was dropped without replying.
+    pub body: Option<Resp>,
+}
+
+/// Error returned by [`RpcClient::call`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    /// The peer `Channel` was dropped before a response arrived.
+    Closed,
+    /// The server dropped the [`Responder`] without replying.
+    Dropped,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Closed => write!(f, "rpc channel closed"),
+            RpcError::Dropped => write!(f, "responder dropped without replying"),
+        }
+    }
+}
+
+impl Error for RpcError {}
+
+type Pending<Resp> = HashMap<u64, oneshot::Sender<Option<Resp>>>;
+
+#[derive(Debug)]
+struct Calls<Resp> {
+    pending: Pending<Resp>,
+    closed: bool,
+}
+
+/// Removes the entry of a call from the pending calls once the call returns or is cancelled.
+struct PendingCall<'a, Resp> {
+    calls: &'a Mutex<Calls<Resp>>,
+    id: u64,
+}
+
+impl<Resp> Drop for PendingCall<'_, Resp> {
+    fn drop(&mut self) {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pending
+            .remove(&self.id);
+    }
+}
+
+/// The calling end of an RPC channel.
+///
+/// Any number of calls may be in flight at once, and responses may arrive in any order.
+/// The client can be cloned to issue calls from several tasks.
+#[derive(Debug)]
+pub struct RpcClient<Req, Resp> {
+    sender: ChannelSender<Request<Req>>,
+    calls: Arc<Mutex<Calls<Resp>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl<Req, Resp> Clone for RpcClient<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            calls: self.calls.clone(),
+            next_id: self.next_id.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> RpcClient<Req, Resp>
+where
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    /// Wraps a channel whose peer is (or will be wrapped by) an [`RpcServer`].
+    ///
+    /// This spawns a task that routes responses to their callers, so it must be called from
+    /// within a Tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to issue calls over.
+    ///
+    /// # Returns
+    ///
+    /// * `RpcClient<Req, Resp>` - Returns the client.
+    pub fn new(channel: Channel<Request<Req>, Response<Resp>>) -> Self {
+        let (sender, receiver) = channel.split();
+        let calls = Arc::new(Mutex::new(Calls {
+            pending: HashMap::new(),
+            closed: false,
+        }));
+
+        tokio::spawn(dispatch(receiver, calls.clone()));
+
+        Self {
+            sender,
+            calls,
+            next_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Sends a request and waits for the matching response.
+    ///
+    /// # Arguments
+    ///
+    /// * `req` - The request to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Resp, RpcError>` - Returns the response, or an error if the server went away or
+    ///   dropped the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (client, mut server) = tokio_bichannel::rpc::channel::<u32, u32>(10);
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some((n, responder)) = server.recv().await {
+    ///         responder.send(n * 2);
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(client.call(21).await.unwrap(), 42);
+    /// # }
+    /// ```
+    pub async fn call(&self, req: Req) -> Result<Resp, RpcError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+            if calls.closed {
+                return Err(RpcError::Closed);
+            }
+            calls.pending.insert(id, tx);
+        }
+        let _pending = PendingCall {
+            calls: &self.calls,
+            id,
+        };
+
+        if self.sender.send(Request { id, body: req }).await.is_err() {
+            return Err(RpcError::Closed);
+        }
+
+        match rx.await {
+            Ok(Some(resp)) => Ok(resp),
+            Ok(None) => Err(RpcError::Dropped),
+            Err(_) => Err(RpcError::Closed),
+        }
+    }
+}
+
+async fn dispatch<Resp>(
+    mut receiver: ChannelReceiver<Response<Resp>>,
+    calls: Arc<Mutex<Calls<Resp>>>,
+) {
+    while let Some(resp) = receiver.recv().await {
+        if let Some(tx) = calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pending
+            .remove(&resp.id)
+        {
+            let _ = tx.send(resp.body);
+        }
+    }
+
+    let mut calls = calls.lock().unwrap_or_else(PoisonError::into_inner);
+    calls.closed = true;
+    calls.pending.clear();
+}
+
+/// The answering end of an RPC channel.
+#[derive(Debug)]
+pub struct RpcServer<Req, Resp> {
+    sender: ChannelSender<Response<Resp>>,
+    receiver: ChannelReceiver<Request<Req>>,
+}
+
+impl<Req, Resp> RpcServer<Req, Resp> {
+    /// Wraps a channel whose peer is (or will be wrapped by) an [`RpcClient`].
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - The channel to answer calls over.
+    ///
+    /// # Returns
+    ///
+    /// * `RpcServer<Req, Resp>` - Returns the server.
+    pub fn new(channel: Channel<Response<Resp>, Request<Req>>) -> Self {
+        let (sender, receiver) = channel.split();
+        Self { sender, receiver }
+    }
+
+    /// Receives the next request along with a handle to answer it.
+    ///
+    /// A slot for the response is reserved before the request is taken, so at most
+    /// `buffer_size` responders can be outstanding at once.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(Req, Responder<Resp>)>` - Returns the request and its responder, or `None` if every
+    ///   client has been dropped.
+    pub async fn recv(&mut self) -> Option<(Req, Responder<Resp>)> {
+        let permit = self.sender.clone().reserve_owned().await.ok()?;
+        let req = self.receiver.recv().await?;
+        let responder = Responder {
+            id: req.id,
+            permit: Some(permit),
+        };
+
+        Some((req.body, responder))
+    }
+}
+
+/// A handle used to answer a single request received by an [`RpcServer`].
+///
+/// Dropping the handle without calling [`Responder::send`] makes the matching
+/// [`RpcClient::call`] return [`RpcError::Dropped`].
+#[derive(Debug)]
+pub struct Responder<Resp> {
+    id: u64,
+    permit: Option<OwnedPermit<Response<Resp>>>,
+}
+
+impl<Resp> Responder<Resp> {
+    /// The correlation ID of the request being answered.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sends the response back to the caller.
+    ///
+    /// The response is silently discarded if the client has gone away.
+    ///
+    /// # Arguments
+    ///
+    /// * `resp` - The response to send.
+    pub fn send(mut self, resp: Resp) {
+        let permit = self.permit.take().expect("responder already used");
+        permit.send(Response {
+            id: self.id,
+            body: Some(resp),
+        });
+    }
+}
+
+impl<Resp> Drop for Responder<Resp> {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            permit.send(Response {
+                id: self.id,
+                body: None,
+            });
+        }
+    }
+}
+
+/// Creates an RPC channel with the specified buffer size.
+///
+/// This spawns a task that routes responses to their callers, so it must be called from
+/// within a Tokio runtime.
+///
+/// # Arguments
+///
+/// * `buffer_size` - The size of the buffer for each direction.
+///
+/// # Returns
+///
+/// * `(RpcClient<Req, Resp>, RpcServer<Req, Resp>)` - Returns the calling and answering ends.
+pub fn channel<Req, Resp>(buffer_size: usize) -> (RpcClient<Req, Resp>, RpcServer<Req, Resp>)
+where
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    let (client, server) = bichannel(buffer_size);
+    (RpcClient::new(client), RpcServer::new(server))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_out_of_order() {
+        let (client, mut server) = channel::<u64, u64>(10);
+
+        tokio::spawn(async move {
+            let (a, ra) = server.recv().await.unwrap();
+            let (b, rb) = server.recv().await.unwrap();
+
+            rb.send(b + 1);
+            ra.send(a + 1);
+        });
+
+        let c2 = client.clone();
+        let (a, b) = tokio::join!(client.call(10), c2.call(20));
+        assert_eq!(a, Ok(11));
+        assert_eq!(b, Ok(21));
+    }
+
+    #[tokio::test]
+    async fn test_responder_dropped() {
+        let (client, mut server) = channel::<(), ()>(10);
+
+        tokio::spawn(async move {
+            let (_, responder) = server.recv().await.unwrap();
+            drop(responder);
+            server
+        });
+
+        assert_eq!(client.call(()).await, Err(RpcError::Dropped));
+    }
+
+    #[tokio::test]
+    async fn test_server_dropped() {
+        tokio::time::pause();
+        let (client, server) = channel::<(), ()>(10);
+
+        let call = tokio::spawn({
+            let client = client.clone();
+            async move { client.call(()).await }
+        });
+
+        // Lets the call go out before the server is dropped.
+        tokio::time::advance(Duration::from_millis(10)).await;
+        drop(server);
+
+        assert_eq!(call.await.unwrap(), Err(RpcError::Closed));
+        assert_eq!(client.call(()).await, Err(RpcError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_call_cancelled() {
+        tokio::time::pause();
+        let (client, mut server) = channel::<(), ()>(10);
+
+        let call = tokio::time::timeout(Duration::from_millis(10), client.call(()));
+        assert!(call.await.is_err());
+        assert!(client.calls.lock().unwrap().pending.is_empty());
+
+        // The late response is discarded.
+        let (_, responder) = server.recv().await.unwrap();
+        responder.send(());
+        assert!(client.calls.lock().unwrap().pending.is_empty());
+    }
+}
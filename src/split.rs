@@ -0,0 +1,840 @@
+use std::error::Error;
+use std::fmt;
+use std::future::poll_fn;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(feature = "remote")]
+use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+use tokio::sync::mpsc::{channel as create_channel, Receiver, Sender, UnboundedSender, WeakSender};
+use tokio::sync::Notify;
+#[cfg(feature = "futures")]
+use tokio_util::sync::PollSender;
+
+use crate::metrics::{Envelope, Metrics};
+#[cfg(feature = "remote")]
+use crate::remote::RemoteError;
+use crate::{Channel, DeadLetter, DeadLetterReason, Overflow};
+
+/// The queue of a direction.
+///
+/// The receiver is only shared with the senders of a direction that evicts its oldest messages,
+/// so that they can reach into the queue. Other directions own it, and never lock it.
+#[derive(Debug)]
+pub(crate) enum Queue<T> {
+    Owned(Receiver<Envelope<T>>),
+    Shared(Arc<Mutex<Receiver<Envelope<T>>>>),
+}
+
+impl<T> Queue<T> {
+    /// Gives access to the receiver, locking it if it is shared.
+    pub(crate) fn lock(&mut self) -> QueueGuard<'_, T> {
+        match self {
+            Queue::Owned(receiver) => QueueGuard::Owned(receiver),
+            Queue::Shared(receiver) => {
+                QueueGuard::Shared(receiver.lock().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+
+    /// Runs `f` on the receiver, locking it if it is shared.
+    fn with<U>(&self, f: impl FnOnce(&Receiver<Envelope<T>>) -> U) -> U {
+        match self {
+            Queue::Owned(receiver) => f(receiver),
+            Queue::Shared(receiver) => f(&receiver.lock().unwrap_or_else(PoisonError::into_inner)),
+        }
+    }
+}
+
+/// Access to the receiver of a [`Queue`].
+pub(crate) enum QueueGuard<'a, T> {
+    Owned(&'a mut Receiver<Envelope<T>>),
+    Shared(MutexGuard<'a, Receiver<Envelope<T>>>),
+}
+
+impl<T> Deref for QueueGuard<'_, T> {
+    type Target = Receiver<Envelope<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            QueueGuard::Owned(receiver) => receiver,
+            QueueGuard::Shared(receiver) => receiver,
+        }
+    }
+}
+
+impl<T> DerefMut for QueueGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            QueueGuard::Owned(receiver) => receiver,
+            QueueGuard::Shared(receiver) => receiver,
+        }
+    }
+}
+
+/// The configuration and counters of a direction, shared by all of its halves.
+#[derive(Debug)]
+pub(crate) struct DirectionState<T> {
+    pub(crate) name: Option<Arc<str>>,
+    // The name of the direction in tracing spans and events, which falls back to the IDs of the
+    // channels it connects.
+    #[cfg(feature = "tracing")]
+    pub(crate) label: Arc<str>,
+    // The bound of the underlying channel, which is only a sentinel for unbounded directions.
+    pub(crate) max_capacity: usize,
+    pub(crate) unbounded: bool,
+    pub(crate) metrics: Option<Metrics>,
+    pub(crate) overflow: Overflow,
+    pub(crate) ttl: Option<Duration>,
+    pub(crate) dropped: AtomicU64,
+    pub(crate) evicted: AtomicU64,
+    pub(crate) expired: AtomicU64,
+    pub(crate) dead_letters: Mutex<Option<UnboundedSender<DeadLetter<T>>>>,
+    // Weak so that the direction still closes once the receiving half is dropped. Only set for
+    // directions that evict their oldest messages.
+    pub(crate) queue: Weak<Mutex<Receiver<Envelope<T>>>>,
+    // Set once the receiving half is closed or dropped, so that a sending half that was closed
+    // itself can still tell whether the peer is gone.
+    pub(crate) receiver_closed: AtomicBool,
+    pub(crate) receiver_closed_notify: Notify,
+    // The error that ended the stream a remote bridge reads this direction from.
+    #[cfg(feature = "remote")]
+    pub(crate) remote_error: OnceLock<Arc<RemoteError>>,
+}
+
+impl<T> DirectionState<T> {
+    /// Records that the receiving half can no longer receive.
+    pub(crate) fn close_receiver(&self) {
+        self.receiver_closed.store(true, Ordering::Release);
+        self.receiver_closed_notify.notify_waiters();
+    }
+}
+
+/// The sending half of a [`Channel`], created by [`Channel::split`].
+///
+/// The sending half can be cloned to give several tasks a way to write to the peer.
+/// The peer only observes the direction as closed once every clone has been dropped.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::channel;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (chan1, mut chan2) = channel::<String, String>(10);
+/// let (tx, _rx) = chan1.split();
+///
+/// tokio::spawn(async move {
+///     tx.send("Hello from the writer task".to_string()).await.unwrap();
+/// });
+///
+/// assert_eq!(chan2.recv().await.unwrap(), "Hello from the writer task");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChannelSender<S> {
+    pub(crate) id: u64,
+    pub(crate) state: Arc<DirectionState<S>>,
+    pub(crate) sender: Sender<Envelope<S>>,
+    // The sender swapped out by `close`, which still describes the direction without keeping it
+    // open.
+    pub(crate) closed: Option<WeakSender<Envelope<S>>>,
+    #[cfg(feature = "futures")]
+    pub(crate) poll: Option<PollSender<Envelope<S>>>,
+}
+
+impl<S> Clone for ChannelSender<S> {
+    fn clone(&self) -> Self {
+        Self {
+            closed: self.closed.clone(),
+            ..Self::new(self.id, self.state.clone(), self.sender.clone())
+        }
+    }
+}
+
+/// A sending half that does not keep the direction open, created by
+/// [`ChannelSender::downgrade`].
+#[derive(Debug)]
+pub(crate) struct WeakChannelSender<S> {
+    id: u64,
+    state: Arc<DirectionState<S>>,
+    sender: WeakSender<Envelope<S>>,
+}
+
+impl<S> WeakChannelSender<S> {
+    /// Returns a sending half if the direction is still held open by another one.
+    pub(crate) fn upgrade(&self) -> Option<ChannelSender<S>> {
+        let sender = self.sender.upgrade()?;
+        Some(ChannelSender::new(self.id, self.state.clone(), sender))
+    }
+}
+
+/// The receiving half of a [`Channel`], created by [`Channel::split`].
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::channel;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (chan1, chan2) = channel::<String, String>(10);
+/// let (_tx, mut rx) = chan1.split();
+///
+/// tokio::spawn(async move {
+///     chan2.send("Hello from chan2".to_string()).await.unwrap();
+/// });
+///
+/// assert_eq!(rx.recv().await.unwrap(), "Hello from chan2");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChannelReceiver<R> {
+    pub(crate) id: u64,
+    pub(crate) state: Arc<DirectionState<R>>,
+    pub(crate) receiver: Queue<R>,
+}
+
+/// Error returned by [`ChannelSender::reunite`] and [`ChannelReceiver::reunite`] when the
+/// two halves did not come from the same [`Channel`].
+///
+/// Both halves are handed back so that neither is lost.
+#[derive(Debug)]
+pub struct ReuniteError<S, R>(pub ChannelSender<S>, pub ChannelReceiver<R>);
+
+impl<S, R> fmt::Display for ReuniteError<S, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tried to reunite halves that are not from the same channel"
+        )
+    }
+}
+
+impl<S: fmt::Debug, R: fmt::Debug> Error for ReuniteError<S, R> {}
+
+pub(crate) fn reunite<S, R>(
+    sender: ChannelSender<S>,
+    receiver: ChannelReceiver<R>,
+) -> Result<Channel<S, R>, ReuniteError<S, R>> {
+    if sender.id == receiver.id {
+        Ok(Channel { sender, receiver })
+    } else {
+        Err(ReuniteError(sender, receiver))
+    }
+}
+
+impl<S> ChannelSender<S> {
+    pub(crate) fn new(id: u64, state: Arc<DirectionState<S>>, sender: Sender<Envelope<S>>) -> Self {
+        Self {
+            id,
+            state,
+            sender,
+            closed: None,
+            #[cfg(feature = "futures")]
+            poll: None,
+        }
+    }
+
+    /// Creates a handle that can send on the direction only while this half, or a clone of it,
+    /// keeps it open.
+    pub(crate) fn downgrade(&self) -> WeakChannelSender<S> {
+        WeakChannelSender {
+            id: self.id,
+            state: self.state.clone(),
+            sender: self.sender.downgrade(),
+        }
+    }
+
+    /// Wraps a message for the queue of the direction.
+    pub(crate) fn envelope(&self, s: S) -> Envelope<S> {
+        Envelope::new(s, &self.state)
+    }
+
+    /// Records a message that was just queued.
+    pub(crate) fn sent(&self) {
+        if let Some(metrics) = &self.state.metrics {
+            metrics.record_sent(1);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            channel = self.label(),
+            id = self.id,
+            queue_len = self.queue_len(),
+            "sent message"
+        );
+    }
+
+    /// Starts timing a wait for `n` slots, if the wait is worth recording.
+    pub(crate) fn start_blocking(&self, n: usize) -> Option<Instant> {
+        let full = self.sender.capacity() < n;
+
+        #[cfg(feature = "tracing")]
+        if full {
+            tracing::debug!(
+                channel = self.label(),
+                id = self.id,
+                queue_len = self.queue_len(),
+                "send blocked on full direction"
+            );
+        }
+
+        (full || self.state.metrics.is_some()).then(Instant::now)
+    }
+
+    /// Ends a wait started with [`ChannelSender::start_blocking`].
+    pub(crate) fn stop_blocking(&self, since: Option<Instant>) {
+        let Some(since) = since else {
+            return;
+        };
+
+        if let Some(metrics) = &self.state.metrics {
+            metrics.record_blocked(since);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            channel = self.label(),
+            id = self.id,
+            waited = ?since.elapsed(),
+            "send unblocked"
+        );
+    }
+
+    /// The name of the direction this half sends on, if one was set with [`Direction::name`].
+    ///
+    /// [`Direction::name`]: crate::Direction::name
+    pub fn name(&self) -> Option<&str> {
+        self.state.name.as_deref()
+    }
+
+    /// The name of the direction in tracing spans and events.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn label(&self) -> &str {
+        &self.state.label
+    }
+
+    /// Creates the span of an operation on this half, so that its events can be correlated.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, op: &'static str) -> tracing::Span {
+        tracing::trace_span!("channel", op, channel = self.label(), id = self.id)
+    }
+
+    /// Sends a message to the peer.
+    ///
+    /// If the direction is full, this waits for room unless the [`Overflow`] policy of the
+    /// direction says otherwise. If the peer stopped receiving and the direction has a
+    /// dead-letter sink, the message goes to the sink and this returns `Ok(())`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or the message
+    ///   back if the channel is closed or the direction is full and its policy is
+    ///   [`Overflow::Reject`]. [`ChannelSender::try_send`] tells the two cases apart.
+    pub async fn send(&self, s: S) -> Result<(), SendError<S>> {
+        let send = async {
+            if self.state.overflow != Overflow::Block {
+                return self.send_now(s).map_err(|e| SendError(e.into_inner()));
+            }
+
+            match self.reserve().await {
+                Ok(permit) => {
+                    permit.send(s);
+                    Ok(())
+                }
+                Err(_) => self.undeliverable(s).map_err(SendError),
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let send = tracing::Instrument::instrument(send, self.span("send"));
+
+        send.await
+    }
+
+    /// Attempts to send a message to the peer without blocking.
+    ///
+    /// If the peer stopped receiving and the direction has a dead-letter sink, the message goes
+    /// to the sink and this returns `Ok(())`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), TrySendError<S>>` - Returns `Ok(())` if the message was sent, or an error if the channel is full or closed.
+    pub fn try_send(&self, s: S) -> Result<(), TrySendError<S>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span("try_send").entered();
+
+        self.send_now(s)
+    }
+
+    /// Waits until the peer can no longer receive messages, for example because it was dropped.
+    ///
+    /// This also completes immediately once this handle has been closed.
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+
+    /// Returns the number of messages that can currently be sent without waiting, which is `0`
+    /// once this handle has been closed, or `None` if the direction is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        match self.closed {
+            Some(_) => Some(0),
+            None if self.state.unbounded => None,
+            None => Some(self.sender.capacity()),
+        }
+    }
+
+    /// Returns the capacity the direction was created with, or `None` if it is unbounded.
+    pub fn max_capacity(&self) -> Option<usize> {
+        (!self.state.unbounded).then_some(self.state.max_capacity)
+    }
+
+    /// Returns the number of slots of the direction that are in use, which is the number of
+    /// messages the peer has yet to receive plus any reserved slots.
+    ///
+    /// Once this handle has been closed, this is only known while another clone of this half
+    /// keeps the direction open, and is `0` otherwise.
+    pub fn queue_len(&self) -> usize {
+        let capacity = match &self.closed {
+            Some(weak) => weak
+                .upgrade()
+                .map_or(self.state.max_capacity, |s| s.capacity()),
+            None => self.sender.capacity(),
+        };
+        self.state.max_capacity - capacity
+    }
+
+    /// Returns whether both handles send on the same direction of the same channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The handle to compare against.
+    pub fn same_channel(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+
+    /// Stops sending on this handle.
+    ///
+    /// Once every clone of this half has been closed or dropped, the peer receives the messages
+    /// that are still queued and then sees the direction as closed. Messages can still be
+    /// received from the peer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tokio_bichannel::channel;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, mut chan2) = channel::<u32, u32>(10);
+    /// let (mut tx, _rx) = chan1.split();
+    ///
+    /// tx.send(1).await.unwrap();
+    /// tx.close();
+    ///
+    /// assert!(tx.send(2).await.is_err());
+    /// assert_eq!(chan2.recv().await, Some(1));
+    /// assert_eq!(chan2.recv().await, None);
+    /// # }
+    /// ```
+    pub fn close(&mut self) {
+        if self.closed.is_some() {
+            return;
+        }
+
+        // Swap in a sender whose receiver is already gone so that every send
+        // path reports the direction as closed without special-casing it.
+        let (closed, _) = create_channel(1);
+        self.closed = Some(std::mem::replace(&mut self.sender, closed).downgrade());
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(channel = self.label(), id = self.id, "closed sending half");
+
+        #[cfg(feature = "futures")]
+        {
+            self.poll = None;
+        }
+    }
+
+    /// Returns whether this handle can no longer send, either because it was closed with
+    /// [`ChannelSender::close`] or because the peer stopped receiving.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Returns whether the peer can no longer receive, regardless of whether this handle was
+    /// closed.
+    pub(crate) fn is_peer_closed(&self) -> bool {
+        match self.closed {
+            Some(_) => self.state.receiver_closed.load(Ordering::Acquire),
+            None => self.sender.is_closed(),
+        }
+    }
+
+    /// Waits until the peer can no longer receive, regardless of whether this handle was closed.
+    pub(crate) async fn peer_closed(&self) {
+        if self.closed.is_none() {
+            return self.sender.closed().await;
+        }
+
+        loop {
+            // Created before checking the flag so that a close in between is not missed.
+            let notified = self.state.receiver_closed_notify.notified();
+            if self.state.receiver_closed.load(Ordering::Acquire) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Attempts to put the two halves of a [`Channel`] back together.
+    ///
+    /// Succeeds only if both halves came from the same call to [`Channel::split`].
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The receiving half to reunite with.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Channel<S, R>, ReuniteError<S, R>>` - Returns the original channel, or both halves if they don't match.
+    pub fn reunite<R>(
+        self,
+        other: ChannelReceiver<R>,
+    ) -> Result<Channel<S, R>, ReuniteError<S, R>> {
+        reunite(self, other)
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    pub(crate) fn new(id: u64, state: Arc<DirectionState<R>>, receiver: Queue<R>) -> Self {
+        Self {
+            id,
+            state,
+            receiver,
+        }
+    }
+
+    /// Gives access to the queue of the direction, locking it if senders may evict from it.
+    ///
+    /// The lock is never held across an `.await` or while blocking the thread, and only senders
+    /// evicting a message contend for it.
+    pub(crate) fn queue(&mut self) -> QueueGuard<'_, R> {
+        self.receiver.lock()
+    }
+
+    /// Unwraps a message taken from the queue, recording it in the metrics.
+    ///
+    /// Returns `None` if the message outlived the time to live of the direction, after handing
+    /// it to the dead-letter sink.
+    pub(crate) fn open(&self, envelope: Envelope<R>) -> Option<R> {
+        if envelope.is_expired(self.state.ttl) {
+            self.state.expired.fetch_add(1, Ordering::Relaxed);
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(channel = self.label(), id = self.id, "expired message");
+
+            let _ = self
+                .state
+                .dead_letter(envelope.value, DeadLetterReason::Expired);
+            return None;
+        }
+
+        if let Some(metrics) = &self.state.metrics {
+            metrics.record_received(&envelope);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            channel = self.label(),
+            id = self.id,
+            queue_len = self.queue_len(),
+            "received message"
+        );
+
+        Some(envelope.value)
+    }
+
+    /// The name of the direction this half receives from, if one was set with [`Direction::name`].
+    ///
+    /// [`Direction::name`]: crate::Direction::name
+    pub fn name(&self) -> Option<&str> {
+        self.state.name.as_deref()
+    }
+
+    /// The name of the direction in tracing spans and events.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn label(&self) -> &str {
+        &self.state.label
+    }
+
+    /// Creates the span of an operation on this half, so that its events can be correlated.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, op: &'static str) -> tracing::Span {
+        tracing::trace_span!("channel", op, channel = self.label(), id = self.id)
+    }
+
+    /// Receives a message from the peer.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<R>` - Returns `Some(message)` if a message was received, or `None` if the channel is closed.
+    pub async fn recv(&mut self) -> Option<R> {
+        #[cfg(feature = "tracing")]
+        let span = self.span("recv");
+
+        let recv = async {
+            loop {
+                let envelope = poll_fn(|cx| self.queue().poll_recv(cx)).await?;
+                if let Some(r) = self.open(envelope) {
+                    return Some(r);
+                }
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let recv = tracing::Instrument::instrument(recv, span);
+
+        recv.await
+    }
+
+    /// Attempts to receive a message from the peer without blocking.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, TryRecvError>` - Returns `Ok(message)` if a message was received, or an error if the channel is empty or closed.
+    pub fn try_recv(&mut self) -> Result<R, TryRecvError> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span("try_recv").entered();
+
+        loop {
+            let envelope = self.queue().try_recv()?;
+            if let Some(r) = self.open(envelope) {
+                return Ok(r);
+            }
+        }
+    }
+
+    /// Returns the number of messages waiting to be received.
+    pub fn queue_len(&self) -> usize {
+        self.receiver.with(Receiver::len)
+    }
+
+    /// Stops receiving from the peer.
+    ///
+    /// Messages that are already queued can still be received, but the peer can no longer send.
+    pub fn close(&mut self) {
+        self.queue().close();
+        self.state.close_receiver();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            channel = self.label(),
+            id = self.id,
+            "closed receiving half"
+        );
+    }
+
+    /// Returns whether the peer can no longer send, either because this half was closed with
+    /// [`ChannelReceiver::close`] or because every sender of the direction is gone.
+    ///
+    /// Messages may still be queued even if this returns `true`.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.with(Receiver::is_closed)
+    }
+
+    /// Attempts to put the two halves of a [`Channel`] back together.
+    ///
+    /// Succeeds only if both halves came from the same call to [`Channel::split`].
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The sending half to reunite with.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Channel<S, R>, ReuniteError<S, R>>` - Returns the original channel, or both halves if they don't match.
+    pub fn reunite<S>(self, other: ChannelSender<S>) -> Result<Channel<S, R>, ReuniteError<S, R>> {
+        reunite(other, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel;
+
+    #[tokio::test]
+    async fn test_split_concurrent() {
+        let (chan1, chan2) = channel::<u32, u32>(10);
+        let (tx1, mut rx1) = chan1.split();
+        let (tx2, mut rx2) = chan2.split();
+
+        let writer = tokio::spawn(async move {
+            for i in 0..5 {
+                tx1.send(i).await.unwrap();
+            }
+        });
+
+        let echo = tokio::spawn(async move {
+            while let Some(i) = rx2.recv().await {
+                tx2.send(i * 10).await.unwrap();
+            }
+        });
+
+        for i in 0..5 {
+            assert_eq!(rx1.recv().await.unwrap(), i * 10);
+        }
+
+        writer.await.unwrap();
+        assert!(rx1.recv().await.is_none());
+        echo.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reunite() {
+        let (chan1, chan2) = channel::<String, String>(10);
+        let (tx1, rx1) = chan1.split();
+        let (tx2, rx2) = chan2.split();
+
+        let err = tx1.reunite(rx2).unwrap_err();
+        let (tx1, rx2) = (err.0, err.1);
+
+        let mut chan1 = rx1.reunite(tx1).unwrap();
+        let chan2 = tx2.reunite(rx2).unwrap();
+
+        chan2.send("Hello from chan2".to_string()).await.unwrap();
+        assert_eq!(chan1.recv().await.unwrap(), "Hello from chan2");
+    }
+
+    #[tokio::test]
+    async fn test_close_keeps_accessors() {
+        let (chan1, _chan2) = channel::<u32, u32>(10);
+        let (tx, _rx) = chan1.split();
+        let mut closed = tx.clone();
+
+        tx.send(1).await.unwrap();
+        closed.close();
+
+        assert!(closed.is_closed());
+        assert!(closed.same_channel(&tx));
+        assert_eq!(closed.max_capacity(), Some(10));
+        assert_eq!(closed.capacity(), Some(0));
+        assert_eq!(closed.queue_len(), 1);
+        assert_eq!(closed.clone().max_capacity(), Some(10));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing_events() {
+        use std::fmt::{Debug, Write};
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        use crate::{ChannelBuilder, Direction};
+
+        /// Collects every span and event as a line of `field=value` pairs, spans prefixed with
+        /// `span `.
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<String>>>);
+
+        struct Line(String);
+
+        impl Visit for Line {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                write!(self.0, "{}={:?} ", field.name(), value).unwrap();
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+                let mut line = Line(String::from("span "));
+                attrs.record(&mut line);
+                self.0.lock().unwrap().push(line.0);
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut line = Line(String::new());
+                event.record(&mut line);
+                self.0.lock().unwrap().push(line.0);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let capture = Capture::default();
+        let _guard = tracing::subscriber::set_default(capture.clone());
+
+        let (mut chan1, mut chan2) = ChannelBuilder::new(1)
+            .left_to_right(Direction::bounded(1).name("jobs"))
+            .build::<u32, u32>();
+
+        chan1.send(1).await.unwrap();
+        let (sent, received) = tokio::join!(chan1.send(2), chan2.recv());
+        sent.unwrap();
+        assert_eq!(received, Some(1));
+        chan1.close_send();
+
+        let events = capture.0.lock().unwrap().clone();
+        let find = |message: &str| {
+            events
+                .iter()
+                .find(|e| e.starts_with(&format!("message={} ", message)))
+                .unwrap_or_else(|| panic!("no {:?} event in {:#?}", message, events))
+                .clone()
+        };
+
+        assert!(find("sent message").contains("channel=\"jobs\""));
+        assert!(find("received message").contains("channel=\"jobs\""));
+        assert!(find("closed sending half").contains("channel=\"jobs\""));
+
+        let blocked = find("send blocked on full direction");
+        assert!(blocked.contains("channel=\"jobs\""));
+        assert!(blocked.contains("queue_len=1"));
+
+        // Operations run in spans carrying the same name as their events.
+        let spans = |op: &str| {
+            events
+                .iter()
+                .filter(|e| e.starts_with(&format!("span op=\"{}\" ", op)))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert!(spans("send")[0].contains("channel=\"jobs\""));
+        assert!(spans("recv")[0].contains("channel=\"jobs\""));
+
+        // Directions without a name are labelled with the channels they connect.
+        capture.0.lock().unwrap().clear();
+        let (chan1, _chan2) = crate::channel::<u32, u32>(1);
+        chan1.try_send(1).unwrap();
+        let (tx, _) = chan1.split();
+        let label = format!("channel=\"{}->{}\"", tx.id, tx.id + 1);
+        let events = capture.0.lock().unwrap().clone();
+        assert!(events.iter().all(|e| e.contains(&label)), "{:#?}", events);
+    }
+}
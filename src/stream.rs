@@ -0,0 +1,187 @@
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::sync::mpsc::channel as create_channel;
+use tokio_util::sync::{PollSendError, PollSender};
+
+use crate::metrics::Envelope;
+use crate::{Channel, ChannelReceiver, ChannelSender};
+
+impl<S: Send> ChannelSender<S> {
+    fn poll_sender(&mut self) -> &mut PollSender<Envelope<S>> {
+        let sender = &self.sender;
+        self.poll
+            .get_or_insert_with(|| PollSender::new(sender.clone()))
+    }
+}
+
+/// Returns a [`PollSender`] that is already closed.
+///
+/// `PollSendError` can only be created by a `PollSender`, so errors of the inner sender, which
+/// carries envelopes, are rebuilt with one of these.
+fn closed_poll_sender<S: Send>() -> PollSender<S> {
+    let mut closed = PollSender::new(create_channel(1).0);
+    closed.close();
+    closed
+}
+
+/// Sends messages to the peer, waiting for capacity in [`Sink::poll_ready`] the same way
+/// [`PollSender`] does.
+impl<S: Send> Sink<S> for ChannelSender<S> {
+    type Error = PollSendError<S>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match ready!(this.poll_sender().poll_reserve(cx)) {
+            Ok(()) => Poll::Ready(Ok(())),
+            // `start_send` hands the message to the dead-letter sink.
+            Err(_) if this.routes_undeliverable() => Poll::Ready(Ok(())),
+            Err(_) => match closed_poll_sender().poll_reserve(cx) {
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                _ => unreachable!("a closed PollSender is never ready"),
+            },
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: S) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let envelope = this.envelope(item);
+
+        match this.poll_sender().send_item(envelope) {
+            Ok(()) => {
+                this.sent();
+                Ok(())
+            }
+            Err(e) => {
+                let envelope = e.into_inner().expect("send_item hands back the item");
+                this.undeliverable(envelope.value)
+                    .map_err(|s| closed_poll_sender().send_item(s).unwrap_err())
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Yields messages from the peer until it is dropped.
+impl<R> Stream for ChannelReceiver<R> {
+    type Item = R;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        let this = self.get_mut();
+        loop {
+            let Some(envelope) = ready!(this.queue().poll_recv(cx)) else {
+                return Poll::Ready(None);
+            };
+            if let Some(r) = this.open(envelope) {
+                return Poll::Ready(Some(r));
+            }
+        }
+    }
+}
+
+impl<S: Send, R> Sink<S> for Channel<S, R> {
+    type Error = PollSendError<S>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().sender).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: S) -> Result<(), Self::Error> {
+        Pin::new(&mut self.get_mut().sender).start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().sender).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().sender).poll_close(cx)
+    }
+}
+
+impl<S, R> Stream for Channel<S, R> {
+    type Item = R;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<R>> {
+        Pin::new(&mut self.get_mut().receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+
+    use crate::channel;
+
+    #[tokio::test]
+    async fn test_stream_combinators() {
+        let (chan1, chan2) = channel::<u32, u32>(4);
+
+        tokio::spawn(async move {
+            for i in 0..10 {
+                chan1.send(i).await.unwrap();
+            }
+        });
+
+        let evens: Vec<u32> = chan2
+            .filter(|i| std::future::ready(i % 2 == 0))
+            .collect()
+            .await;
+        assert_eq!(evens, vec![0, 2, 4, 6, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_sink_backpressure() {
+        let (mut chan1, mut chan2) = channel::<u32, u32>(1);
+
+        chan1.feed(1).await.unwrap();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        assert!(chan1.poll_ready_unpin(&mut cx).is_pending());
+
+        assert_eq!(chan2.recv().await.unwrap(), 1);
+        SinkExt::send(&mut chan1, 2).await.unwrap();
+        assert_eq!(chan2.recv().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_futures_split_forward() {
+        let (mut chan1, chan2) = channel::<u32, u32>(4);
+
+        let (sink, stream) = StreamExt::split(chan2);
+        let echo = tokio::spawn(stream.map(|i| Ok(i * 2)).forward(sink));
+
+        for i in 0..10 {
+            chan1.send(i).await.unwrap();
+            assert_eq!(chan1.recv().await.unwrap(), i * 2);
+        }
+
+        drop(chan1);
+        echo.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_halves_forward() {
+        let (mut chan1, chan2) = channel::<u32, u32>(4);
+
+        let (tx, rx) = chan2.split();
+        let echo = tokio::spawn(rx.map(Ok).forward(tx));
+
+        chan1.send(7).await.unwrap();
+        assert_eq!(chan1.recv().await.unwrap(), 7);
+
+        drop(chan1);
+        echo.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use tokio::sync::mpsc::error::{SendTimeoutError, TrySendError};
+use tokio::time::{timeout_at, Instant};
+
+use crate::{Channel, ChannelReceiver, ChannelSender, Overflow};
+
+/// Error returned by [`Channel::recv_timeout`] and [`Channel::recv_until`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    /// No message arrived before the deadline.
+    Timeout,
+    /// The peer stopped sending and every queued message has been received.
+    Closed,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting on channel"),
+            RecvTimeoutError::Closed => write!(f, "channel closed"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+impl<S> ChannelSender<S> {
+    /// Sends a message to the peer, waiting at most `timeout` for capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `timeout` - How long to wait for capacity.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendTimeoutError<S>>` - Returns `Ok(())` if the message was sent, or the message back if
+    ///   the timeout elapsed or the channel is closed.
+    pub async fn send_timeout(&self, s: S, timeout: Duration) -> Result<(), SendTimeoutError<S>> {
+        self.send_until(s, Instant::now() + timeout).await
+    }
+
+    /// Sends a message to the peer, waiting for capacity until `deadline`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `deadline` - When to give up waiting for capacity.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendTimeoutError<S>>` - Returns `Ok(())` if the message was sent, or the message back if
+    ///   the deadline passed or the channel is closed.
+    pub async fn send_until(&self, s: S, deadline: Instant) -> Result<(), SendTimeoutError<S>> {
+        if self.state.overflow != Overflow::Block {
+            return self.send_now(s).map_err(|e| match e {
+                TrySendError::Full(s) => SendTimeoutError::Timeout(s),
+                TrySendError::Closed(s) => SendTimeoutError::Closed(s),
+            });
+        }
+
+        match timeout_at(deadline, self.reserve()).await {
+            Ok(Ok(permit)) => {
+                permit.send(s);
+                Ok(())
+            }
+            Ok(Err(_)) => self.undeliverable(s).map_err(SendTimeoutError::Closed),
+            Err(_) => Err(SendTimeoutError::Timeout(s)),
+        }
+    }
+}
+
+impl<R> ChannelReceiver<R> {
+    /// Receives a message from the peer, waiting at most `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait for a message.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, RecvTimeoutError>` - Returns the message, or an error if the timeout elapsed or the channel is closed.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<R, RecvTimeoutError> {
+        self.recv_until(Instant::now() + timeout).await
+    }
+
+    /// Receives a message from the peer, waiting until `deadline`.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - When to give up waiting for a message.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, RecvTimeoutError>` - Returns the message, or an error if the deadline passed or the channel is closed.
+    pub async fn recv_until(&mut self, deadline: Instant) -> Result<R, RecvTimeoutError> {
+        match timeout_at(deadline, self.recv()).await {
+            Ok(Some(r)) => Ok(r),
+            Ok(None) => Err(RecvTimeoutError::Closed),
+            Err(_) => Err(RecvTimeoutError::Timeout),
+        }
+    }
+}
+
+impl<S, R> Channel<S, R> {
+    /// Sends a message through the channel, waiting at most `timeout` for capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `timeout` - How long to wait for capacity.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendTimeoutError<S>>` - Returns `Ok(())` if the message was sent, or the message back if
+    ///   the timeout elapsed or the channel is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tokio::sync::mpsc::error::SendTimeoutError;
+    /// use tokio_bichannel::channel;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (chan1, _chan2) = channel::<String, String>(1);
+    ///
+    /// chan1.send("fills the buffer".to_string()).await.unwrap();
+    ///
+    /// match chan1.send_timeout("Hello".to_string(), Duration::from_millis(10)).await {
+    ///     Err(SendTimeoutError::Timeout(msg)) => assert_eq!(msg, "Hello"),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// # }
+    /// ```
+    pub async fn send_timeout(&self, s: S, timeout: Duration) -> Result<(), SendTimeoutError<S>> {
+        self.sender.send_timeout(s, timeout).await
+    }
+
+    /// Sends a message through the channel, waiting for capacity until `deadline`.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    /// * `deadline` - When to give up waiting for capacity.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendTimeoutError<S>>` - Returns `Ok(())` if the message was sent, or the message back if
+    ///   the deadline passed or the channel is closed.
+    pub async fn send_until(&self, s: S, deadline: Instant) -> Result<(), SendTimeoutError<S>> {
+        self.sender.send_until(s, deadline).await
+    }
+
+    /// Receives a message from the channel, waiting at most `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait for a message.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, RecvTimeoutError>` - Returns the message, or an error if the timeout elapsed or the channel is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tokio_bichannel::{channel, RecvTimeoutError};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (mut chan1, _chan2) = channel::<String, String>(10);
+    ///
+    /// let result = chan1.recv_timeout(Duration::from_millis(10)).await;
+    /// assert_eq!(result, Err(RecvTimeoutError::Timeout));
+    /// # }
+    /// ```
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<R, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout).await
+    }
+
+    /// Receives a message from the channel, waiting until `deadline`.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - When to give up waiting for a message.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, RecvTimeoutError>` - Returns the message, or an error if the deadline passed or the channel is closed.
+    pub async fn recv_until(&mut self, deadline: Instant) -> Result<R, RecvTimeoutError> {
+        self.receiver.recv_until(deadline).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel;
+
+    #[tokio::test]
+    async fn test_send_timeout() {
+        let (chan1, mut chan2) = channel::<u32, u32>(1);
+
+        chan1.send(1).await.unwrap();
+        match chan1.send_timeout(2, Duration::from_millis(10)).await {
+            Err(SendTimeoutError::Timeout(2)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert_eq!(chan2.recv().await, Some(1));
+        chan1
+            .send_timeout(3, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(chan2.recv().await, Some(3));
+
+        drop(chan2);
+        match chan1.send_until(4, Instant::now()).await {
+            Err(SendTimeoutError::Closed(4)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recv_timeout() {
+        let (chan1, mut chan2) = channel::<u32, u32>(1);
+
+        let deadline = Instant::now() + Duration::from_millis(10);
+        assert_eq!(
+            chan2.recv_until(deadline).await,
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert!(Instant::now() >= deadline);
+
+        chan1.send(1).await.unwrap();
+        assert_eq!(chan2.recv_timeout(Duration::from_millis(10)).await, Ok(1));
+
+        drop(chan1);
+        assert_eq!(
+            chan2.recv_timeout(Duration::from_secs(10)).await,
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+}
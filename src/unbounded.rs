@@ -0,0 +1,162 @@
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+use tokio::sync::mpsc::{
+    unbounded_channel as create_unbounded_channel, UnboundedReceiver, UnboundedSender,
+};
+
+/// A bidirectional channel with no limit on the number of queued messages in either direction.
+///
+/// Because sends never wait for capacity, [`UnboundedChannel::send`] is synchronous and can be
+/// called from outside of an async context. It is backed by
+/// [`tokio::sync::mpsc::unbounded_channel`], so it has none of the per-direction options of a
+/// [`Channel`]; build a [`Channel`] with [`Direction::unbounded`] directions for those.
+///
+/// [`Channel`]: crate::Channel
+/// [`Direction::unbounded`]: crate::Direction::unbounded
+///
+/// # Examples
+///
+/// ```
+/// # use tokio_bichannel::unbounded_channel;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let (mut l, mut r) = unbounded_channel::<String, String>();
+///
+/// l.send("Hello from chan1".to_string()).unwrap();
+/// r.send("Hello from chan2".to_string()).unwrap();
+///
+/// assert_eq!(r.recv().await.unwrap(), "Hello from chan1");
+/// assert_eq!(l.recv().await.unwrap(), "Hello from chan2");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct UnboundedChannel<S, R> {
+    sender: UnboundedSender<S>,
+    receiver: UnboundedReceiver<R>,
+}
+
+impl<S, R> UnboundedChannel<S, R> {
+    /// Sends a message through the channel without waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The message to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), SendError<S>>` - Returns `Ok(())` if the message was sent successfully, or an error if the peer is gone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let (channel, _peer) = tokio_bichannel::unbounded_channel::<String, String>();
+    /// channel.send("Hello".to_string()).unwrap();
+    /// ```
+    pub fn send(&self, s: S) -> Result<(), SendError<S>> {
+        self.sender.send(s)
+    }
+
+    /// Receives a message from the channel.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<R>` - Returns `Some(message)` if a message was received, or `None` if the channel is closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let (mut channel, _) = tokio_bichannel::unbounded_channel::<String, String>();
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// while let Some(msg) = channel.recv().await {
+    ///     println!("Received: {}", msg);
+    /// }
+    /// # });
+    /// ```
+    pub async fn recv(&mut self) -> Option<R> {
+        self.receiver.recv().await
+    }
+
+    /// Attempts to receive a message from the channel without blocking.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<R, TryRecvError>` - Returns `Ok(message)` if a message was received, or an error if the channel is empty or closed.
+    ///
+    /// # Examples
+    /// ```
+    /// # let (mut channel, _peer) = tokio_bichannel::unbounded_channel::<String, String>();
+    /// match channel.try_recv() {
+    ///     Ok(msg) => println!("Received: {}", msg),
+    ///     Err(e) => println!("Error: {:?}", e),
+    /// }
+    /// ```
+    pub fn try_recv(&mut self) -> Result<R, TryRecvError> {
+        self.receiver.try_recv()
+    }
+}
+
+/// Creates an unbounded bidirectional channel.
+///
+/// # Returns
+///
+/// * `(UnboundedChannel<T, U>, UnboundedChannel<U, T>)` - Returns a tuple of two `UnboundedChannel` instances.
+///
+/// # Examples
+///
+/// ```
+/// use tokio_bichannel::unbounded_channel;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (chan1, mut chan2) = unbounded_channel::<String, String>();
+///
+///     std::thread::spawn(move || {
+///         chan1.send("Hello from a plain thread".to_string()).unwrap();
+///     });
+///
+///     assert_eq!(chan2.recv().await.unwrap(), "Hello from a plain thread");
+/// }
+/// ```
+pub fn unbounded_channel<T, U>() -> (UnboundedChannel<T, U>, UnboundedChannel<U, T>) {
+    let (ls, lr) = create_unbounded_channel();
+    let (rs, rr) = create_unbounded_channel();
+
+    (
+        UnboundedChannel {
+            sender: ls,
+            receiver: rr,
+        },
+        UnboundedChannel {
+            sender: rs,
+            receiver: lr,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_recv() {
+        let (mut chan1, mut chan2) = unbounded_channel::<u32, String>();
+
+        for i in 0..1000 {
+            chan1.send(i).unwrap();
+        }
+        chan2.send("done".to_string()).unwrap();
+
+        for i in 0..1000 {
+            assert_eq!(chan2.recv().await.unwrap(), i);
+        }
+        assert_eq!(chan1.try_recv().unwrap(), "done");
+        assert!(chan1.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_peer_dropped() {
+        let (chan1, chan2) = unbounded_channel::<u32, u32>();
+        drop(chan2);
+
+        assert_eq!(chan1.send(1).unwrap_err().0, 1);
+    }
+}